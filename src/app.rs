@@ -1,8 +1,44 @@
-use crate::asset::{CompanyInfo, Stock};
+use crate::asset::{
+    Analysts, AssetClass, BarPeriod, ChartRange, CompanyInfo, CorporateActions, Earnings,
+    EarningsRelease, ForexRates, Fundamentals, InsiderTransaction, LiveData, LiveStats,
+    NewsArticle, Peer, Prices, Quote, Split, Stock,
+};
+use crate::error::StonksError;
+use crate::market::Market;
 use crate::search_page::SearchEngine;
-use crate::util::TabsState;
-use simsearch::SimSearch;
-use std::collections::HashMap;
+use crate::service::{DataCommand, DataEvent, DataService};
+use crate::subscription::{ConnectionStatus, FeedEvent, SubscriptionManager};
+use crate::util::{self, TabsState};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Shortest bar built from live trades, in seconds
+const DEFAULT_LIVE_BAR_SECONDS: i64 = 60;
+/// Currencies in the rate matrix unless configured, the first is the base
+const DEFAULT_CURRENCIES: &str = "USD,EUR,GBP,JPY";
+/// How often the quote is refreshed
+const QUOTE_REFRESH: Duration = Duration::from_secs(60);
+/// How often the rate matrix is refreshed while it is shown
+const RATES_REFRESH: Duration = Duration::from_secs(60);
+/// How often the news is refreshed while it is shown
+const NEWS_REFRESH: Duration = Duration::from_secs(5 * 60);
+/// How often the watchlist's earnings calendar is refreshed while it is shown
+const EARNINGS_REFRESH: Duration = Duration::from_secs(60 * 60);
+
+pub const CHART_TAB: usize = 0;
+pub const RATES_TAB: usize = 1;
+pub const NEWS_TAB: usize = 2;
+pub const EARNINGS_TAB: usize = 3;
+pub const FUNDAMENTALS_TAB: usize = 4;
+pub const ANALYSTS_TAB: usize = 5;
+pub const PEERS_TAB: usize = 6;
+pub const DIVIDENDS_TAB: usize = 7;
+pub const INSIDERS_TAB: usize = 8;
 
 pub enum InputMode {
     Normal,
@@ -14,7 +50,6 @@ pub enum State {
     Normal,
 }
 pub struct App<'a> {
-    pub title: &'a str,
     pub show_chart: bool,
     pub should_quit: bool,
     pub tabs: TabsState<'a>,
@@ -22,40 +57,203 @@ pub struct App<'a> {
     pub input: String,
     pub company: Option<CompanyInfo>,
     pub symbol: String,
+    pub asset_class: AssetClass,
     pub config: HashMap<String, String>,
     pub state: State,
     pub securities: Vec<Stock>,
+    /// Symbol lists that failed to load and so are missing from the search
+    pub missing_symbols: Vec<String>,
     pub search_engine: Option<SearchEngine>,
+    /// Exchange and symbol lists asked for and not in yet. The search index
+    /// is built once they are all in.
+    pub securities_pending: usize,
+    pub service: DataService,
+    pub chart_range: ChartRange,
+    /// Waiting on the data service for the company or chart
+    pub loading: bool,
+    /// Last failure getting data, shown instead of the chart
+    pub error: Option<StonksError>,
+    pub subscriptions: SubscriptionManager,
+    pub feed_status: ConnectionStatus,
+    /// Trades streamed so far, by symbol
+    pub live: HashMap<String, LiveStats>,
+    /// Shortest bar built from live trades, charts with longer bars use theirs
+    pub live_bar_seconds: i64,
+    /// Day change and ranges of the current symbol, moved along by live trades
+    pub quote: Option<Quote>,
+    quote_requested: Option<Instant>,
+    /// Headlines about `news_symbol`, newest first
+    pub news: Vec<NewsArticle>,
+    news_symbol: Option<String>,
+    news_requested: Option<Instant>,
+    pub news_selection: usize,
+    /// Outcome of the last open or copy, shown until the selection moves
+    pub news_status: Option<String>,
+    /// Symbols streamed along with the selected one
+    pub watchlist: Vec<String>,
+    /// Earnings reports of the current company, only stocks have them
+    pub earnings: Option<Earnings>,
+    /// Upcoming earnings reports of the watchlist, soonest first
+    pub watchlist_earnings: Vec<EarningsRelease>,
+    watchlist_earnings_requested: Option<Instant>,
+    /// Analyst ratings and price targets of the current company, `None`
+    /// while loading
+    pub analysts: Option<Result<Analysts, StonksError>>,
+    /// Dividends and splits of the current company, `None` while loading
+    pub actions: Option<Result<CorporateActions, StonksError>>,
+    /// Whether the chart is adjusted for splits, which finnhub.io already does
+    /// for daily and longer bars but not intraday ones
+    pub adjusted: bool,
+    /// Peers of `peers_symbol` with their quotes, `None` while loading
+    pub peers: Option<Result<Vec<Peer>, StonksError>>,
+    peers_symbol: Option<String>,
+    pub peers_selection: usize,
+    /// Key metrics of `fundamentals_symbol`, `None` while loading
+    pub fundamentals: Option<Result<Fundamentals, StonksError>>,
+    fundamentals_symbol: Option<String>,
+    /// Last year of insider transactions in `insiders_symbol`, newest first,
+    /// `None` while loading
+    pub insiders: Option<Result<Vec<InsiderTransaction>, StonksError>>,
+    insiders_symbol: Option<String>,
+    /// First transaction shown in the insiders table
+    pub insiders_scroll: usize,
+    /// Rows and columns of the rate matrix
+    pub currencies: Vec<String>,
+    pub rates: Option<ForexRates>,
+    rates_requested: Option<Instant>,
+    /// Index into `rate_pairs` of the pair picked in the rate matrix
+    pub rates_selection: usize,
 }
 
 impl<'a> App<'a> {
-    pub fn new(title: &'a str, symbol: String, config: HashMap<String, String>) -> App<'a> {
+    pub fn new(
+        symbol: String,
+        config: HashMap<String, String>,
+        service: DataService,
+        mut subscriptions: SubscriptionManager,
+    ) -> App<'a> {
+        let live_bar_seconds = config
+            .get("live_bar_seconds")
+            .and_then(|seconds| seconds.parse().ok())
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(DEFAULT_LIVE_BAR_SECONDS);
+        let currencies = config_list(&config, "currencies", DEFAULT_CURRENCIES);
+        let watchlist = config_list(&config, "watchlist", "");
+        for symbol in &watchlist {
+            subscriptions.watch(symbol);
+        }
         App {
-            title,
             should_quit: false,
-            tabs: TabsState::new(vec!["Tab0", "Tab1"]),
+            tabs: TabsState::new(vec![
+                "Chart",
+                "Rates",
+                "News",
+                "Earnings",
+                "Fundamentals",
+                "Analysts",
+                "Peers",
+                "Dividends",
+                "Insiders",
+            ]),
             show_chart: true,
             input_mode: InputMode::Normal,
             input: String::new(),
             company: None,
             symbol,
+            asset_class: AssetClass::Stock,
             config,
             state: State::Normal,
             securities: Vec::new(),
+            missing_symbols: Vec::new(),
             search_engine: None,
+            securities_pending: 0,
+            service,
+            chart_range: ChartRange::OneYear,
+            loading: false,
+            error: None,
+            subscriptions,
+            feed_status: ConnectionStatus::Connecting,
+            live: HashMap::new(),
+            live_bar_seconds,
+            quote: None,
+            quote_requested: None,
+            currencies,
+            rates: None,
+            rates_requested: None,
+            rates_selection: 0,
+            news: Vec::new(),
+            news_symbol: None,
+            news_requested: None,
+            news_selection: 0,
+            news_status: None,
+            watchlist,
+            earnings: None,
+            watchlist_earnings: Vec::new(),
+            watchlist_earnings_requested: None,
+            fundamentals: None,
+            fundamentals_symbol: None,
+            insiders: None,
+            insiders_symbol: None,
+            insiders_scroll: 0,
+            analysts: None,
+            peers: None,
+            peers_symbol: None,
+            peers_selection: 0,
+            actions: None,
+            adjusted: true,
         }
     }
 
-    pub fn on_up(&mut self) {}
+    pub fn on_up(&mut self) {
+        match self.tabs.index {
+            RATES_TAB if self.rates_selection > 0 => self.rates_selection -= 1,
+            NEWS_TAB if self.news_selection > 0 => {
+                self.news_selection -= 1;
+                self.news_status = None;
+            }
+            PEERS_TAB if self.peers_selection > 0 => self.peers_selection -= 1,
+            INSIDERS_TAB if self.insiders_scroll > 0 => self.insiders_scroll -= 1,
+            _ => {}
+        }
+    }
 
-    pub fn on_down(&mut self) {}
+    pub fn on_down(&mut self) {
+        match self.tabs.index {
+            RATES_TAB if self.rates_selection + 1 < self.rate_pairs().len() => {
+                self.rates_selection += 1
+            }
+            NEWS_TAB if self.news_selection + 1 < self.news.len() => {
+                self.news_selection += 1;
+                self.news_status = None;
+            }
+            PEERS_TAB if self.peers_selection + 1 < self.peer_count() => self.peers_selection += 1,
+            INSIDERS_TAB if self.insiders_scroll + 1 < self.insider_count() => {
+                self.insiders_scroll += 1
+            }
+            _ => {}
+        }
+    }
 
     pub fn on_right(&mut self) {
         self.tabs.next();
+        self.subscribe_rates();
+        self.refresh_rates();
+        self.refresh_news();
+        self.refresh_earnings_calendar();
+        self.refresh_fundamentals();
+        self.refresh_insiders();
+        self.refresh_peers();
     }
 
     pub fn on_left(&mut self) {
         self.tabs.previous();
+        self.subscribe_rates();
+        self.refresh_rates();
+        self.refresh_news();
+        self.refresh_earnings_calendar();
+        self.refresh_fundamentals();
+        self.refresh_insiders();
+        self.refresh_peers();
     }
 
     pub fn on_key(&mut self, c: char) {
@@ -71,34 +269,719 @@ impl<'a> App<'a> {
                 InputMode::Normal => self.input_mode = InputMode::Editing,
                 InputMode::Editing => self.input_mode = InputMode::Normal,
             },
-            _ => {
-                if let InputMode::Editing = self.input_mode {
-                    self.search_text_input(c);
+            _ => match self.input_mode {
+                InputMode::Editing => self.search_text_input(c),
+                InputMode::Normal => match c {
+                    'o' if self.tabs.index == NEWS_TAB => self.open_news(),
+                    'c' if self.tabs.index == NEWS_TAB => self.copy_news(),
+                    'a' => self.adjusted = !self.adjusted,
+                    _ => {
+                        if let Some(digit) = c.to_digit(10) {
+                            self.select_chart_range(digit as usize);
+                        }
+                    }
+                },
+            },
+        }
+    }
+
+    /// Selects the chart range bound to number key `key`, 1 being the shortest
+    fn select_chart_range(&mut self, key: usize) {
+        if key == 0 || key > ChartRange::ALL.len() {
+            return;
+        }
+        let range = ChartRange::ALL[key - 1];
+        if range == self.chart_range {
+            return;
+        }
+        self.chart_range = range;
+        if self.company.is_some() {
+            self.loading = true;
+            self.service.send(DataCommand::LoadPrices {
+                symbol: self.symbol.clone(),
+                asset_class: self.asset_class,
+                range,
+            });
+        } else {
+            self.load_company();
+        }
+    }
+
+    /// Asks for the symbol lists of the exchanges in the `exchanges` config,
+    /// just `US` by default, and the `crypto_exchanges` config, none by
+    /// default or every one for `all`
+    pub fn load_securities(&mut self) {
+        for exchange in config_list(&self.config, "exchanges", "US") {
+            self.load_security_list(AssetClass::Stock, exchange);
+        }
+        for (key, asset_class) in &[
+            ("crypto_exchanges", AssetClass::Crypto),
+            ("forex_exchanges", AssetClass::Forex),
+        ] {
+            let exchanges = config_list(&self.config, key, "");
+            if exchanges.iter().any(|exchange| exchange == "ALL") {
+                self.securities_pending += 1;
+                self.service.send(DataCommand::LoadExchanges {
+                    asset_class: *asset_class,
+                });
+                continue;
+            }
+            for exchange in exchanges {
+                self.load_security_list(*asset_class, exchange);
+            }
+        }
+    }
+
+    fn load_security_list(&mut self, asset_class: AssetClass, exchange: String) {
+        self.securities_pending += 1;
+        self.service.send(DataCommand::LoadSecurities {
+            asset_class,
+            exchange,
+        });
+    }
+
+    /// Counts off a list that came in, and rebuilds the search index once
+    /// there are no more to wait for
+    fn security_list_done(&mut self) {
+        self.securities_pending = self.securities_pending.saturating_sub(1);
+        if self.securities_pending == 0 {
+            self.search_engine = Some(SearchEngine::new(&mut self.securities));
+            // Listed forex symbols may have come in
+            self.subscribe_rates();
+        }
+    }
+
+    /// Streams every currency against the base while the rate matrix is shown,
+    /// which is enough to move all of its rates
+    fn subscribe_rates(&mut self) {
+        let pairs: Vec<String> = match self.currencies.split_first() {
+            Some((base, others)) if self.tabs.index == RATES_TAB => others
+                .iter()
+                .map(|currency| self.forex_symbol(base, currency))
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.subscriptions.set_rates(&pairs);
+    }
+
+    /// Asks for the rate matrix if it is shown and out of date
+    fn refresh_rates(&mut self) {
+        if self.tabs.index != RATES_TAB || self.currencies.is_empty() {
+            return;
+        }
+        if let Some(requested) = self.rates_requested {
+            if requested.elapsed() < RATES_REFRESH {
+                return;
+            }
+        }
+        self.rates_requested = Some(Instant::now());
+        self.service.send(DataCommand::LoadRates {
+            base: self.currencies[0].clone(),
+        });
+    }
+
+    /// Asks for news about the current symbol if the news is shown and out of
+    /// date or about another symbol
+    fn refresh_news(&mut self) {
+        if self.tabs.index != NEWS_TAB {
+            return;
+        }
+        let fresh = self
+            .news_requested
+            .is_some_and(|requested| requested.elapsed() < NEWS_REFRESH);
+        if fresh && self.news_symbol.as_ref() == Some(&self.symbol) {
+            return;
+        }
+        self.news_requested = Some(Instant::now());
+        self.news_symbol = Some(self.symbol.clone());
+        self.service.send(DataCommand::LoadNews {
+            symbol: self.symbol.clone(),
+            asset_class: self.asset_class,
+        });
+    }
+
+    /// Asks for the watchlist's upcoming earnings if they are shown and out of
+    /// date
+    fn refresh_earnings_calendar(&mut self) {
+        if self.tabs.index != EARNINGS_TAB || self.watchlist.is_empty() {
+            return;
+        }
+        if let Some(requested) = self.watchlist_earnings_requested {
+            if requested.elapsed() < EARNINGS_REFRESH {
+                return;
+            }
+        }
+        self.watchlist_earnings_requested = Some(Instant::now());
+        self.service.send(DataCommand::LoadEarningsCalendar {
+            symbols: self.watchlist.clone(),
+        });
+    }
+
+    /// Asks for the key metrics of the current company if they are shown and
+    /// about another symbol or failed to load
+    fn refresh_fundamentals(&mut self) {
+        if self.tabs.index != FUNDAMENTALS_TAB || self.asset_class != AssetClass::Stock {
+            return;
+        }
+        let failed = matches!(self.fundamentals, Some(Err(_)));
+        if !failed && self.fundamentals_symbol.as_ref() == Some(&self.symbol) {
+            return;
+        }
+        self.fundamentals = None;
+        self.fundamentals_symbol = Some(self.symbol.clone());
+        self.service.send(DataCommand::LoadFundamentals {
+            symbol: self.symbol.clone(),
+        });
+    }
+
+    /// Asks for the insider transactions of the current company if they are
+    /// shown and about another symbol or failed to load
+    fn refresh_insiders(&mut self) {
+        if self.tabs.index != INSIDERS_TAB || self.asset_class != AssetClass::Stock {
+            return;
+        }
+        let failed = matches!(self.insiders, Some(Err(_)));
+        if !failed && self.insiders_symbol.as_ref() == Some(&self.symbol) {
+            return;
+        }
+        self.insiders = None;
+        self.insiders_scroll = 0;
+        self.insiders_symbol = Some(self.symbol.clone());
+        self.service.send(DataCommand::LoadInsiders {
+            symbol: self.symbol.clone(),
+        });
+    }
+
+    fn insider_count(&self) -> usize {
+        match &self.insiders {
+            Some(Ok(insiders)) => insiders.len(),
+            _ => 0,
+        }
+    }
+
+    /// Asks for the peers of the current company if they are shown and about
+    /// another symbol or failed to load
+    fn refresh_peers(&mut self) {
+        if self.tabs.index != PEERS_TAB || self.asset_class != AssetClass::Stock {
+            return;
+        }
+        let failed = matches!(self.peers, Some(Err(_)));
+        if !failed && self.peers_symbol.as_ref() == Some(&self.symbol) {
+            return;
+        }
+        self.peers = None;
+        self.peers_selection = 0;
+        self.peers_symbol = Some(self.symbol.clone());
+        self.service.send(DataCommand::LoadPeers {
+            symbol: self.symbol.clone(),
+        });
+    }
+
+    /// Where the current symbol trades, found from the symbol lists
+    pub fn market(&self) -> Market {
+        match self.asset_class {
+            AssetClass::Crypto => Market::Crypto,
+            AssetClass::Forex => Market::Forex,
+            AssetClass::Stock => {
+                let exchange = self
+                    .securities
+                    .iter()
+                    .find(|stock| {
+                        stock.asset_class == AssetClass::Stock && stock.symbol == self.symbol
+                    })
+                    .map(|stock| stock.exchange.as_str());
+                Market::for_stock(&self.symbol, exchange)
+            }
+        }
+    }
+
+    fn peer_count(&self) -> usize {
+        match &self.peers {
+            Some(Ok(peers)) => peers.len(),
+            _ => 0,
+        }
+    }
+
+    /// Makes the selected peer the current symbol
+    fn jump_to_peer(&mut self) {
+        let symbol = match &self.peers {
+            Some(Ok(peers)) => match peers.get(self.peers_selection) {
+                Some(peer) => peer.symbol.clone(),
+                None => return,
+            },
+            _ => return,
+        };
+        self.symbol = symbol;
+        self.asset_class = AssetClass::Stock;
+        self.load_company();
+    }
+
+    fn selected_news(&self) -> Option<&NewsArticle> {
+        self.news.get(self.news_selection)
+    }
+
+    /// Opens the selected article in the default browser
+    fn open_news(&mut self) {
+        let url = match self.selected_news() {
+            Some(article) => article.url.clone(),
+            None => return,
+        };
+        self.news_status = Some(match webbrowser::open(&url) {
+            Ok(_) => String::from("Opened in browser"),
+            Err(err) => {
+                error!("Could not open {}: {}", url, err);
+                format!("Could not open browser: {}", err)
+            }
+        });
+    }
+
+    /// Copies the link of the selected article
+    fn copy_news(&mut self) {
+        let url = match self.selected_news() {
+            Some(article) => article.url.clone(),
+            None => return,
+        };
+        self.news_status = Some(match util::copy_to_clipboard(&url) {
+            Ok(_) => String::from("Link copied"),
+            Err(err) => {
+                error!("Could not copy {}: {}", url, err);
+                format!("Could not copy link: {}", err)
+            }
+        });
+    }
+
+    /// Every ordered pair of different currencies in the rate matrix, row by
+    /// row
+    pub fn rate_pairs(&self) -> Vec<(&str, &str)> {
+        let mut pairs = Vec::new();
+        for from in &self.currencies {
+            for to in &self.currencies {
+                if from != to {
+                    pairs.push((from.as_str(), to.as_str()));
                 }
             }
         }
+        pairs
+    }
+
+    /// Feed symbol of a currency pair. Uses the listed symbol for it if there
+    /// is one, the inverse pair if that is listed instead.
+    fn forex_symbol(&self, from: &str, to: &str) -> String {
+        let listed = |from: &str, to: &str| {
+            let display_symbol = format!("{}/{}", from, to);
+            self.securities
+                .iter()
+                .find(|stock| {
+                    stock.asset_class == AssetClass::Forex && stock.display_symbol == display_symbol
+                })
+                .map(|stock| stock.symbol.clone())
+        };
+        listed(from, to)
+            .or_else(|| listed(to, from))
+            .unwrap_or_else(|| format!("OANDA:{}_{}", from, to))
+    }
+
+    /// Charts the pair picked in the rate matrix
+    fn chart_selected_rate(&mut self) {
+        let symbol = match self.rate_pairs().get(self.rates_selection) {
+            Some((from, to)) => self.forex_symbol(from, to),
+            None => return,
+        };
+        if symbol != self.symbol || self.company.is_none() {
+            self.symbol = symbol;
+            self.asset_class = AssetClass::Forex;
+            self.load_company();
+        }
+    }
+
+    /// Bars of the chart, adjusted for splits or not as switched and the
+    /// resolution of the bars requires
+    pub fn chart_prices(&self) -> Option<Cow<'_, Prices>> {
+        let prices = &self.company.as_ref()?.prices;
+        let raw = self.chart_range.is_intraday();
+        match self.splits() {
+            Some(splits) if self.adjusted && raw => Some(Cow::Owned(prices.split_adjusted(splits))),
+            Some(splits) if !self.adjusted && !raw => {
+                Some(Cow::Owned(prices.split_unadjusted(splits)))
+            }
+            _ => Some(Cow::Borrowed(prices)),
+        }
+    }
+
+    /// Splits of the current company, `None` if it never split
+    pub fn splits(&self) -> Option<&[Split]> {
+        match &self.actions {
+            Some(Ok(actions)) if !actions.splits.is_empty() => Some(&actions.splits),
+            _ => None,
+        }
+    }
+
+    /// Asks for the profile and chart of the current symbol and moves the live
+    /// feed over to it
+    pub fn load_company(&mut self) {
+        self.subscriptions.set_active(&self.symbol);
+        self.company = None;
+        self.quote = None;
+        self.error = None;
+        self.loading = true;
+        self.service.send(DataCommand::LoadCompany {
+            symbol: self.symbol.clone(),
+            asset_class: self.asset_class,
+            range: self.chart_range,
+        });
+        self.load_quote();
+        self.refresh_news();
+        self.refresh_fundamentals();
+        self.refresh_insiders();
+        if self.peers_symbol.as_ref() != Some(&self.symbol) {
+            // The old peers stop streaming until the new ones are loaded
+            self.subscriptions.set_peers(&[]);
+            self.refresh_peers();
+        }
+        self.earnings = None;
+        self.analysts = None;
+        self.actions = None;
+        if self.asset_class == AssetClass::Stock {
+            self.service.send(DataCommand::LoadCorporateActions {
+                symbol: self.symbol.clone(),
+            });
+            self.service.send(DataCommand::LoadEarnings {
+                symbol: self.symbol.clone(),
+            });
+            self.service.send(DataCommand::LoadAnalysts {
+                symbol: self.symbol.clone(),
+            });
+        }
+    }
+
+    fn load_quote(&mut self) {
+        self.quote_requested = Some(Instant::now());
+        self.service.send(DataCommand::LoadQuote {
+            symbol: self.symbol.clone(),
+            asset_class: self.asset_class,
+        });
+    }
+
+    /// Takes in a result from the data service. Results for a symbol or range
+    /// that is no longer selected are dropped.
+    pub fn on_data(&mut self, event: DataEvent) {
+        match event {
+            DataEvent::Exchanges {
+                asset_class,
+                result: Ok(exchanges),
+            } => {
+                for exchange in exchanges {
+                    self.load_security_list(asset_class, exchange.to_uppercase());
+                }
+                self.security_list_done();
+            }
+            DataEvent::Exchanges {
+                asset_class,
+                result: Err(err),
+            } => {
+                // The chart is unaffected, the search box lists what is missing
+                error!("Could not get list of {:?} exchanges: {}", asset_class, err);
+                let list = format!("{:?} exchanges", asset_class);
+                if !self.missing_symbols.contains(&list) {
+                    self.missing_symbols.push(list);
+                }
+                self.security_list_done();
+            }
+            DataEvent::Securities {
+                asset_class,
+                exchange,
+                result: Ok(securities),
+            } => {
+                // Replaces an earlier list from the same exchange
+                self.securities
+                    .retain(|stock| stock.asset_class != asset_class || stock.exchange != exchange);
+                self.securities.extend(securities);
+                self.missing_symbols.retain(|list| *list != exchange);
+                self.security_list_done();
+            }
+            DataEvent::Securities {
+                exchange,
+                result: Err(err),
+                ..
+            } => {
+                error!("Could not get list of securities on {}: {}", exchange, err);
+                if !self.missing_symbols.contains(&exchange) {
+                    self.missing_symbols.push(exchange);
+                }
+                self.security_list_done();
+            }
+            DataEvent::Company { symbol, result } if symbol == self.symbol => {
+                self.loading = false;
+                match result {
+                    Ok(mut company) => {
+                        // Trades may have come in while the profile loaded
+                        if let Some(live) = self.live.get(&company.ticker) {
+                            company.prices.live_price = live.price;
+                        }
+                        if company.asset_class != AssetClass::Stock {
+                            if let Some(stock) =
+                                self.securities.iter().find(|stock| stock.symbol == symbol)
+                            {
+                                company.name = stock.description.clone();
+                            }
+                        }
+                        self.company = Some(company);
+                    }
+                    Err(err) => {
+                        error!("Error getting {}: {}", symbol, err);
+                        self.error = Some(err);
+                    }
+                }
+            }
+            DataEvent::Quote { symbol, result } if symbol == self.symbol => match result {
+                Ok(mut quote) => {
+                    if let Some(live) = self.live.get(&symbol) {
+                        // Only trades newer than the quote move it along
+                        if live.time / 1000 > quote.timestamp as u64 {
+                            quote.add_trade(live.price);
+                        }
+                    }
+                    self.quote = Some(quote);
+                }
+                // The rest of the footer is still worth showing
+                Err(err) => warn!("No quote for {}: {}", symbol, err),
+            },
+            DataEvent::News { symbol, result } if symbol == self.symbol => {
+                // A refresh keeps the same article selected
+                let selected = self.selected_news().map(|article| article.url.clone());
+                self.news_selection = 0;
+                match result {
+                    Ok(news) => {
+                        if let Some(selected) = selected {
+                            self.news_selection = news
+                                .iter()
+                                .position(|article| article.url == selected)
+                                .unwrap_or(0);
+                        }
+                        self.news = news;
+                        self.news_status = None;
+                    }
+                    Err(err) => {
+                        error!("Could not get news for {}: {}", symbol, err);
+                        self.news.clear();
+                        self.news_status = Some(format!("No news: {}", err));
+                    }
+                }
+            }
+            DataEvent::Earnings { symbol, result } if symbol == self.symbol => match result {
+                Ok(earnings) => self.earnings = Some(earnings),
+                // Only the earnings tab and chart markers go without
+                Err(err) => warn!("No earnings for {}: {}", symbol, err),
+            },
+            DataEvent::Analysts { symbol, result } if symbol == self.symbol => {
+                if let Err(err) = &result {
+                    error!("Could not get analyst ratings for {}: {}", symbol, err);
+                }
+                self.analysts = Some(result);
+            }
+            DataEvent::CorporateActions { symbol, result } if symbol == self.symbol => {
+                if let Err(err) = &result {
+                    error!("Could not get dividends and splits of {}: {}", symbol, err);
+                }
+                self.actions = Some(result);
+            }
+            DataEvent::Peers { symbol, result } if symbol == self.symbol => {
+                match &result {
+                    Ok(peers) => {
+                        let symbols: Vec<String> =
+                            peers.iter().map(|peer| peer.symbol.clone()).collect();
+                        self.subscriptions.set_peers(&symbols);
+                    }
+                    Err(err) => error!("Could not get peers of {}: {}", symbol, err),
+                }
+                self.peers = Some(result);
+            }
+            DataEvent::Fundamentals { symbol, result } if symbol == self.symbol => {
+                if let Err(err) = &result {
+                    error!("Could not get fundamentals for {}: {}", symbol, err);
+                }
+                self.fundamentals = Some(result);
+            }
+            DataEvent::Insiders { symbol, result } if symbol == self.symbol => {
+                if let Err(err) = &result {
+                    error!("Could not get insider transactions of {}: {}", symbol, err);
+                }
+                self.insiders = Some(result);
+            }
+            DataEvent::EarningsCalendar { result } => match result {
+                Ok(releases) => self.watchlist_earnings = releases,
+                Err(err) => error!("Could not get the watchlist's earnings: {}", err),
+            },
+            DataEvent::Rates {
+                base,
+                result: Ok(rates),
+            } => {
+                if self.currencies.first() == Some(&base) {
+                    self.rates = Some(rates);
+                }
+            }
+            DataEvent::Rates {
+                base,
+                result: Err(err),
+            } => {
+                // The matrix keeps the last rates, the chart is unaffected
+                error!("Could not get exchange rates for {}: {}", base, err);
+            }
+            DataEvent::Prices {
+                symbol,
+                range,
+                result,
+            } if symbol == self.symbol && range == self.chart_range => {
+                self.loading = false;
+                match (result, &mut self.company) {
+                    (Ok(mut prices), Some(company)) => {
+                        prices.live_price = company.prices.live_price;
+                        prices.movement_indicator = company.prices.movement_indicator.clone();
+                        company.prices = prices;
+                        self.error = None;
+                    }
+                    (Ok(_), None) => {}
+                    (Err(err), _) => {
+                        error!("Error getting prices {}: {}", symbol, err);
+                        self.error = Some(err);
+                    }
+                }
+            }
+            _ => debug!("Dropping stale data"),
+        }
+    }
+
+    /// Takes in a trade or status change from the live feed
+    pub fn on_feed(&mut self, event: FeedEvent) {
+        match event {
+            FeedEvent::Trades(trades) => {
+                for trade in &trades {
+                    self.on_trade(trade);
+                }
+            }
+            FeedEvent::Status(status) => {
+                info!("Live feed {:?}", status);
+                self.feed_status = status;
+            }
+        }
+    }
+
+    fn on_trade(&mut self, trade: &LiveData) {
+        self.live
+            .entry(trade.symbol.clone())
+            .or_default()
+            .add(trade);
+        if let Some(rates) = &mut self.rates {
+            // Forex pairs stream as e.g. OANDA:EUR_USD
+            let pair = trade.symbol.split_once(':').map_or("", |(_, pair)| pair);
+            let mut currencies = pair.splitn(2, ['_', '/']);
+            if let (Some(from), Some(to)) = (currencies.next(), currencies.next()) {
+                rates.update(from, to, trade.price);
+            }
+        }
+        if trade.symbol != self.symbol {
+            return;
+        }
+        if let Some(quote) = &mut self.quote {
+            quote.add_trade(trade.price);
+        }
+        if let Some(company) = &mut self.company {
+            if company.ticker != trade.symbol {
+                return;
+            }
+            // Up or down on the day, tick to tick until the quote is in
+            let up = match &self.quote {
+                Some(quote) => quote.change() >= 0.0,
+                None => trade.price > company.prices.live_price,
+            };
+            company.prices.movement_indicator = if up {
+                String::from("▲")
+            } else {
+                String::from("▼")
+            };
+            company.prices.live_price = trade.price;
+            // Keeps the chart moving between fetches
+            let period = match self.chart_range.bar_period() {
+                BarPeriod::Seconds(seconds) => {
+                    BarPeriod::Seconds(seconds.max(self.live_bar_seconds))
+                }
+                calendar => calendar,
+            };
+            company.prices.add_trade(trade, period);
+        }
     }
 
     fn search_text_input(&mut self, c: char) {
         self.input.push(c);
+        self.state = State::Search;
     }
 
     pub fn on_backspace(&mut self) {
         self.input.pop();
+        if self.input.is_empty() {
+            self.state = State::Normal;
+        }
     }
 
+    /// Selects the best match for the search input, or the input itself as a
+    /// symbol if nothing matches
     pub fn on_enter(&mut self) {
-        self.state = State::Search;
-        //self.symbol = self.input.to_uppercase().clone();
-        self.company = None;
-        //self.input.clear();
+        if let InputMode::Normal = self.input_mode {
+            match self.tabs.index {
+                RATES_TAB => self.chart_selected_rate(),
+                PEERS_TAB => self.jump_to_peer(),
+                _ => {}
+            }
+            return;
+        }
+        let input = self.input.trim();
+        if input.is_empty() {
+            return;
+        }
+        let (symbol, asset_class) = self
+            .search_engine
+            .as_ref()
+            .and_then(|search_engine| search_engine.suggestions(input, 1).pop())
+            .map(|stock| (stock.symbol, stock.asset_class))
+            .unwrap_or_else(|| {
+                let symbol = input.to_uppercase();
+                let asset_class = AssetClass::guess(&symbol);
+                (symbol, asset_class)
+            });
+        self.input.clear();
+        self.input_mode = InputMode::Normal;
+        self.state = State::Normal;
+        if symbol != self.symbol || self.company.is_none() {
+            self.symbol = symbol;
+            self.asset_class = asset_class;
+            self.load_company();
+        }
     }
 
     pub fn on_escape(&mut self) {
         self.input_mode = InputMode::Normal;
+        self.state = State::Normal;
     }
 
     pub fn on_tick(&mut self) {
-        // Update progress
+        if let Some(requested) = self.quote_requested {
+            if requested.elapsed() >= QUOTE_REFRESH {
+                self.load_quote();
+            }
+        }
+        self.refresh_rates();
+        self.refresh_news();
+        self.refresh_earnings_calendar();
     }
 }
+
+/// Comma separated codes in the config under `key`, in upper case
+fn config_list(config: &HashMap<String, String>, key: &str, default: &str) -> Vec<String> {
+    config
+        .get(key)
+        .map_or(default, String::as_str)
+        .split(',')
+        .map(|code| code.trim().to_uppercase())
+        .filter(|code| !code.is_empty())
+        .collect()
+}
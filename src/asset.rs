@@ -1,38 +1,158 @@
-use chrono::{offset::Utc, DateTime, Duration};
+use crate::error::StonksError;
+use crate::scheduler::Quota;
+use crate::subscription::{FeedCommand, FeedEvent};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
-use serde::Deserialize;
-use std::{error::Error, net::TcpStream, sync::mpsc, time::SystemTime};
-use tungstenite::{stream::Stream, Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::mpsc,
+};
 //use serde_json::{Result};
-use mpsc::Sender;
-use native_tls::TlsStream;
+use mpsc::{Receiver, Sender};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct CompanyInfo {
     pub name: String,
     pub ticker: String,
     pub country: String,
-    #[serde(rename(deserialize = "marketCapitalization"))]
+    #[serde(rename = "marketCapitalization")]
     pub market_capitalization: f64,
     pub exchange: String,
     pub currency: String,
-    #[serde(rename(deserialize = "finnhubIndustry"))]
+    #[serde(rename = "finnhubIndustry")]
     pub industry: String,
     #[serde(skip)]
     pub prices: Prices,
+    #[serde(skip)]
+    pub asset_class: AssetClass,
+}
+
+impl CompanyInfo {
+    /// Stand-in profile for a pair written `EXCHANGE:PAIR`, finnhub.io only has
+    /// profiles for companies
+    pub fn pair(symbol: &str, asset_class: AssetClass) -> CompanyInfo {
+        let mut parts = symbol.splitn(2, ':');
+        let (exchange, pair) = match (parts.next(), parts.next()) {
+            (Some(exchange), Some(pair)) => (exchange, pair),
+            _ => ("", symbol),
+        };
+        CompanyInfo {
+            name: pair.to_string(),
+            ticker: symbol.to_string(),
+            country: String::new(),
+            market_capitalization: 0.0,
+            exchange: exchange.to_string(),
+            currency: String::new(),
+            industry: String::new(),
+            prices: Prices::default(),
+            asset_class,
+        }
+    }
+}
+
+/// Kind of market a symbol trades on, which decides the endpoints used for it
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetClass {
+    #[default]
+    Stock,
+    Crypto,
+    Forex,
+}
+
+impl AssetClass {
+    /// First segment of the finnhub.io endpoints, e.g. `crypto/candle`
+    pub fn path(&self) -> &'static str {
+        match self {
+            AssetClass::Stock => "stock",
+            AssetClass::Crypto => "crypto",
+            AssetClass::Forex => "forex",
+        }
+    }
+
+    /// Category of the market news covering the class, stocks have news per
+    /// company instead
+    pub fn news_category(&self) -> Option<&'static str> {
+        match self {
+            AssetClass::Stock => None,
+            AssetClass::Crypto => Some("crypto"),
+            AssetClass::Forex => Some("forex"),
+        }
+    }
+
+    /// Best guess for a symbol typed in by hand. Pairs are written
+    /// `EXCHANGE:PAIR`, with the currencies of forex pairs split by `_` or `/`.
+    pub fn guess(symbol: &str) -> AssetClass {
+        match symbol.split_once(':').map(|(_, pair)| pair) {
+            Some(pair) if pair.contains('_') || pair.contains('/') => AssetClass::Forex,
+            Some(_) => AssetClass::Crypto,
+            None => AssetClass::Stock,
+        }
+    }
+}
+
+/// Exchange rates from forex/rates: how much of each currency one unit of
+/// `base` buys
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ForexRates {
+    pub base: String,
+    pub quote: HashMap<String, f64>,
 }
+
+impl ForexRates {
+    /// How much of `to` one unit of `from` buys
+    pub fn rate(&self, from: &str, to: &str) -> Option<f64> {
+        let from = self.units(from)?;
+        let to = self.units(to)?;
+        if from > 0.0 {
+            Some(to / from)
+        } else {
+            None
+        }
+    }
+
+    /// Takes in a live `rate` for `from` in `to`. Only updates a currency
+    /// whose rate against the other one is already known, and never the base.
+    pub fn update(&mut self, from: &str, to: &str, rate: f64) {
+        if rate <= 0.0 {
+            return;
+        }
+        if from == self.base {
+            if to != self.base {
+                self.quote.insert(to.to_string(), rate);
+            }
+        } else if let Some(to_units) = self.units(to) {
+            self.quote.insert(from.to_string(), to_units / rate);
+        } else if let Some(from_units) = self.units(from) {
+            self.quote.insert(to.to_string(), from_units * rate);
+        }
+    }
+
+    fn units(&self, currency: &str) -> Option<f64> {
+        if currency == self.base {
+            Some(1.0)
+        } else {
+            self.quote.get(currency).copied()
+        }
+    }
+}
+
 /// Financial data from the websocket message
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct LiveData {
     #[serde(rename(deserialize = "p"))]
-    pub price: f32,
+    pub price: f64,
     #[serde(rename(deserialize = "s"))]
     pub symbol: String,
+    /// Milliseconds since the unix epoch
     #[serde(rename(deserialize = "t"))]
     pub time: u64,
     #[serde(rename(deserialize = "v"))]
-    pub volume: f32,
+    pub volume: f64,
 }
 
 /// Websocket raw message data
@@ -44,25 +164,801 @@ pub struct Feed {
     pub message_type: String,
 }
 
+impl Feed {
+    pub fn parse(text: &str) -> Result<Feed, StonksError> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Every trade in the message, for any number of symbols
+    pub fn trades(self) -> Vec<LiveData> {
+        self.data.unwrap_or_default()
+    }
+}
+
+/// Running totals of the trades streamed for one symbol
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LiveStats {
+    /// Price of the latest trade
+    pub price: f64,
+    /// Milliseconds since the unix epoch of the latest trade
+    pub time: u64,
+    pub volume: f64,
+    pub trades: usize,
+    /// Sum of price times volume, for the VWAP
+    value: f64,
+}
+
+impl LiveStats {
+    pub fn add(&mut self, trade: &LiveData) {
+        // Trades in a batch are not always in order
+        if trade.time >= self.time {
+            self.price = trade.price;
+            self.time = trade.time;
+        }
+        self.volume += trade.volume;
+        self.value += trade.price * trade.volume;
+        self.trades += 1;
+    }
+
+    /// Volume weighted average price of the trades so far
+    pub fn vwap(&self) -> Option<f64> {
+        if self.volume > 0.0 {
+            Some(self.value / self.volume)
+        } else {
+            None
+        }
+    }
+}
+
+/// A headline from the company-news and news endpoints
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NewsArticle {
+    #[serde(default)]
+    pub category: String,
+    /// Unix timestamp of publication
+    pub datetime: i64,
+    pub headline: String,
+    #[serde(default)]
+    pub id: i64,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub summary: String,
+    pub url: String,
+}
+
+/// Response of the calendar/earnings endpoint
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct EarningsCalendar {
+    #[serde(rename = "earningsCalendar", default)]
+    pub earnings: Vec<EarningsRelease>,
+}
+
+/// A scheduled or past earnings report from the earnings calendar
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct EarningsRelease {
+    pub symbol: String,
+    /// Report date as `YYYY-MM-DD`
+    pub date: String,
+    /// `bmo` before market open, `amc` after market close, `dmh` during
+    /// market hours
+    #[serde(default)]
+    pub hour: String,
+    #[serde(default)]
+    pub quarter: u32,
+    #[serde(default)]
+    pub year: i32,
+    #[serde(rename = "epsActual", default)]
+    pub eps_actual: Option<f64>,
+    #[serde(rename = "epsEstimate", default)]
+    pub eps_estimate: Option<f64>,
+    #[serde(rename = "revenueActual", default)]
+    pub revenue_actual: Option<f64>,
+    #[serde(rename = "revenueEstimate", default)]
+    pub revenue_estimate: Option<f64>,
+}
+
+impl EarningsRelease {
+    /// Unix timestamp of the start of the report day
+    pub fn timestamp(&self) -> Option<i64> {
+        date_to_timestamp(&self.date)
+    }
+}
+
+/// Reported against estimated EPS for a past quarter, from stock/earnings
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct EarningsSurprise {
+    #[serde(default)]
+    pub symbol: String,
+    /// End of the fiscal quarter as `YYYY-MM-DD`
+    pub period: String,
+    pub actual: Option<f64>,
+    pub estimate: Option<f64>,
+}
+
+impl EarningsSurprise {
+    pub fn surprise(&self) -> Option<f64> {
+        Some(self.actual? - self.estimate?)
+    }
+
+    /// Surprise relative to the size of the estimate
+    pub fn surprise_percent(&self) -> Option<f64> {
+        let estimate = self.estimate?;
+        if estimate == 0.0 {
+            return None;
+        }
+        Some(self.surprise()? / estimate.abs() * 100.0)
+    }
+}
+
+/// Earnings reports and surprises of one company
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Earnings {
+    /// Reports around today, oldest first. Past ones have actuals.
+    pub releases: Vec<EarningsRelease>,
+    /// Newest quarter first
+    pub surprises: Vec<EarningsSurprise>,
+}
+
+/// Response of the stock/metric endpoint with `metric=all`
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Fundamentals {
+    #[serde(default)]
+    pub metric: Metrics,
+    #[serde(default)]
+    pub series: MetricSeries,
+}
+
+/// Latest key metrics. Yields and margins are in percent.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Metrics {
+    #[serde(rename = "peBasicExclExtraTTM")]
+    pub pe: Option<f64>,
+    #[serde(rename = "epsBasicExclExtraItemsTTM")]
+    pub eps: Option<f64>,
+    #[serde(rename = "dividendYieldIndicatedAnnual")]
+    pub dividend_yield: Option<f64>,
+    pub beta: Option<f64>,
+    #[serde(rename = "52WeekHigh")]
+    pub year_high: Option<f64>,
+    #[serde(rename = "52WeekLow")]
+    pub year_low: Option<f64>,
+    #[serde(rename = "grossMarginTTM")]
+    pub gross_margin: Option<f64>,
+    #[serde(rename = "operatingMarginTTM")]
+    pub operating_margin: Option<f64>,
+    #[serde(rename = "netProfitMarginTTM")]
+    pub net_margin: Option<f64>,
+    #[serde(rename = "roeTTM")]
+    pub roe: Option<f64>,
+}
+
+/// Past values of metrics by name, e.g. `eps` or `netMargin`
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct MetricSeries {
+    #[serde(default)]
+    pub annual: HashMap<String, Vec<MetricPoint>>,
+    #[serde(default)]
+    pub quarterly: HashMap<String, Vec<MetricPoint>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct MetricPoint {
+    /// End of the fiscal period as `YYYY-MM-DD`
+    pub period: String,
+    #[serde(rename = "v")]
+    pub value: f64,
+}
+
+impl Fundamentals {
+    /// Values of a metric oldest first, quarterly if there are any. The two
+    /// series don't always use the same name for a metric.
+    pub fn history(&self, quarterly: &str, annual: &str) -> Vec<f64> {
+        let series = self
+            .series
+            .quarterly
+            .get(quarterly)
+            .filter(|points| !points.is_empty())
+            .or_else(|| self.series.annual.get(annual));
+        let mut points: Vec<&MetricPoint> = series.into_iter().flatten().collect();
+        points.sort_by(|a, b| a.period.cmp(&b.period));
+        points.into_iter().map(|point| point.value).collect()
+    }
+}
+
+/// Analyst ratings of a stock in one month, from stock/recommendation
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct RecommendationTrend {
+    /// First day of the month as `YYYY-MM-DD`
+    pub period: String,
+    #[serde(rename = "strongBuy", default)]
+    pub strong_buy: u32,
+    #[serde(default)]
+    pub buy: u32,
+    #[serde(default)]
+    pub hold: u32,
+    #[serde(default)]
+    pub sell: u32,
+    #[serde(rename = "strongSell", default)]
+    pub strong_sell: u32,
+}
+
+impl RecommendationTrend {
+    pub fn total(&self) -> u32 {
+        self.strong_buy + self.buy + self.hold + self.sell + self.strong_sell
+    }
+}
+
+/// Consensus of analysts' price targets, from stock/price-target
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct PriceTarget {
+    #[serde(rename = "targetHigh", default)]
+    pub high: f64,
+    #[serde(rename = "targetLow", default)]
+    pub low: f64,
+    #[serde(rename = "targetMean", default)]
+    pub mean: f64,
+    #[serde(rename = "targetMedian", default)]
+    pub median: f64,
+    #[serde(rename = "lastUpdated", default)]
+    pub last_updated: String,
+}
+
+impl PriceTarget {
+    /// Stocks no analyst covers get a target of all zeros
+    pub fn is_empty(&self) -> bool {
+        self.high == 0.0 && self.low == 0.0 && self.mean == 0.0
+    }
+}
+
+/// What analysts make of one company
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Analysts {
+    /// Oldest month first
+    pub recommendations: Vec<RecommendationTrend>,
+    pub price_target: Option<PriceTarget>,
+}
+
+/// A cash dividend from stock/dividend
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Dividend {
+    /// Ex-dividend date as `YYYY-MM-DD`
+    pub date: String,
+    pub amount: f64,
+    /// Amount adjusted for later splits
+    #[serde(rename = "adjustedAmount", default)]
+    pub adjusted_amount: f64,
+    #[serde(rename = "payDate", default)]
+    pub pay_date: Option<String>,
+    #[serde(default)]
+    pub currency: String,
+}
+
+/// A stock split from stock/split, e.g. 1 share into 5 for a 5:1 split
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Split {
+    /// Date as `YYYY-MM-DD`
+    pub date: String,
+    #[serde(rename = "fromFactor")]
+    pub from_factor: f64,
+    #[serde(rename = "toFactor")]
+    pub to_factor: f64,
+}
+
+impl Split {
+    /// Shares held after the split for every share before it
+    pub fn ratio(&self) -> Option<f64> {
+        if self.from_factor > 0.0 && self.to_factor > 0.0 {
+            Some(self.to_factor / self.from_factor)
+        } else {
+            None
+        }
+    }
+}
+
+/// Dividends and splits of one company, oldest first
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CorporateActions {
+    pub dividends: Vec<Dividend>,
+    pub splits: Vec<Split>,
+}
+
+/// Response of the stock/insider-transactions endpoint
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct InsiderTransactions {
+    #[serde(default)]
+    pub data: Vec<InsiderTransaction>,
+}
+
+/// A change in an insider's holding as filed with the SEC
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct InsiderTransaction {
+    pub name: String,
+    /// Shares held after the transaction
+    #[serde(default)]
+    pub share: f64,
+    /// Shares bought, or sold if negative
+    #[serde(default)]
+    pub change: f64,
+    #[serde(rename = "filingDate", default)]
+    pub filing_date: String,
+    #[serde(rename = "transactionDate")]
+    pub transaction_date: String,
+    /// SEC Form 4 code, e.g. `P` for a purchase or `S` for a sale
+    #[serde(rename = "transactionCode", default)]
+    pub transaction_code: String,
+    #[serde(rename = "transactionPrice", default)]
+    pub transaction_price: Option<f64>,
+}
+
+impl InsiderTransaction {
+    /// Bought or sold on the open market, rather than granted, exercised or
+    /// given away
+    pub fn is_open_market(&self) -> bool {
+        self.transaction_code == "P" || self.transaction_code == "S"
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self.transaction_code.as_str() {
+            "P" => "Buy",
+            "S" => "Sell",
+            "A" => "Grant",
+            "M" | "X" => "Exercise",
+            "F" => "Tax",
+            "G" => "Gift",
+            "D" => "Disposal",
+            "C" => "Conversion",
+            _ => "Other",
+        }
+    }
+}
+
+/// Shares and value insiders bought less what they sold on the open market
+/// since the date `since`, formatted as `YYYY-MM-DD`
+pub fn net_insider_buying(transactions: &[InsiderTransaction], since: &str) -> (f64, f64) {
+    transactions
+        .iter()
+        .filter(|transaction| transaction.is_open_market())
+        .filter(|transaction| transaction.transaction_date.as_str() >= since)
+        .fold((0.0, 0.0), |(shares, value), transaction| {
+            let price = transaction.transaction_price.unwrap_or(0.0);
+            (
+                shares + transaction.change,
+                value + transaction.change * price,
+            )
+        })
+}
+
+/// A company in the same industry, with its quote if it could be loaded
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Peer {
+    pub symbol: String,
+    pub quote: Option<Quote>,
+}
+
+/// Data from quote endpoint: api/v1/quote
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Quote {
+    #[serde(rename = "c")]
+    pub current: f64,
+    #[serde(rename = "o")]
+    pub open: f64,
+    #[serde(rename = "h")]
+    pub high: f64,
+    #[serde(rename = "l")]
+    pub low: f64,
+    #[serde(rename = "pc")]
+    pub previous_close: f64,
+    /// Unix timestamp of the quote
+    #[serde(rename = "t", default)]
+    pub timestamp: i64,
+    /// Lowest and highest price over the last 52 weeks, from daily candles
+    #[serde(skip)]
+    pub year_range: Option<(f64, f64)>,
+}
+
+impl Quote {
+    /// Quote from the last daily bar, for pairs the quote endpoint does not
+    /// cover
+    pub fn from_candles(daily: &Prices) -> Option<Quote> {
+        let last = daily.len().checked_sub(1)?;
+        Some(Quote {
+            current: daily.close[last],
+            open: daily.open[last],
+            high: daily.high[last],
+            low: daily.low[last],
+            previous_close: last
+                .checked_sub(1)
+                .map_or(daily.open[last], |i| daily.close[i]),
+            timestamp: daily.timestamps[last],
+            year_range: None,
+        })
+    }
+
+    /// An unknown symbol gets a quote of all zeros
+    pub fn is_empty(&self) -> bool {
+        self.current == 0.0 && self.previous_close == 0.0
+    }
+
+    /// Sets the 52 week range from a year of daily bars
+    pub fn set_year_range(&mut self, daily: &Prices) {
+        let low = daily.low.iter().cloned().fold(f64::INFINITY, f64::min);
+        let high = daily.high.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if low <= high {
+            // A quote without trades today has a low of zero
+            let low = if self.low > 0.0 {
+                low.min(self.low)
+            } else {
+                low
+            };
+            self.year_range = Some((low, high.max(self.high)));
+        }
+    }
+
+    /// Moves the quote along with a live trade
+    pub fn add_trade(&mut self, price: f64) {
+        self.current = price;
+        self.high = self.high.max(price);
+        if self.low <= 0.0 || price < self.low {
+            self.low = price;
+        }
+        if let Some((low, high)) = &mut self.year_range {
+            *low = low.min(price);
+            *high = high.max(price);
+        }
+    }
+
+    /// Change since the previous close
+    pub fn change(&self) -> f64 {
+        self.current - self.previous_close
+    }
+
+    pub fn change_percent(&self) -> Option<f64> {
+        if self.previous_close > 0.0 {
+            Some(self.change() / self.previous_close * 100.0)
+        } else {
+            None
+        }
+    }
+}
+
 /// Data from candles endpoint: api/v1/stock/candle
-#[derive(Deserialize, Debug, Default)]
+///
+/// Each field holds one value per bar, in time order. A `no_data` response
+/// has no bars at all.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 pub struct Prices {
-    #[serde(rename(deserialize = "c"))]
+    #[serde(rename = "o", default)]
+    pub open: Vec<f64>,
+    #[serde(rename = "h", default)]
+    pub high: Vec<f64>,
+    #[serde(rename = "l", default)]
+    pub low: Vec<f64>,
+    #[serde(rename = "c", default)]
     pub close: Vec<f64>,
+    #[serde(rename = "v", default)]
+    pub volume: Vec<f64>,
+    /// Unix timestamp of the start of each bar
+    #[serde(rename = "t", default)]
+    pub timestamps: Vec<i64>,
+    /// `ok` or `no_data`
+    #[serde(rename = "s", default)]
+    pub status: String,
     #[serde(skip)]
     pub live_price: f64,
     #[serde(skip)]
     pub movement_indicator: String,
 }
-#[derive(Deserialize, Debug, Default, Clone)]
+
+impl Prices {
+    pub fn len(&self) -> usize {
+        self.close.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.close.is_empty()
+    }
+
+    pub fn is_no_data(&self) -> bool {
+        self.status == "no_data"
+    }
+
+    pub fn last_close(&self) -> Option<f64> {
+        self.close.last().copied()
+    }
+
+    /// Bars starting between the unix timestamps `from` and `to`, inclusive
+    pub fn between(&self, from: i64, to: i64) -> Prices {
+        let start = self.timestamps.iter().take_while(|t| **t < from).count();
+        let end = self.timestamps.iter().take_while(|t| **t <= to).count();
+        let end = end.max(start);
+        Prices {
+            open: slice(&self.open, start, end),
+            high: slice(&self.high, start, end),
+            low: slice(&self.low, start, end),
+            close: slice(&self.close, start, end),
+            volume: slice(&self.volume, start, end),
+            timestamps: slice(&self.timestamps, start, end),
+            status: self.status.clone(),
+            live_price: self.live_price,
+            movement_indicator: self.movement_indicator.clone(),
+        }
+    }
+
+    /// The bars as if every split had already happened at the start, so
+    /// prices before a split line up with those after it
+    pub fn split_adjusted(&self, splits: &[Split]) -> Prices {
+        self.rescale_splits(splits, false)
+    }
+
+    /// Undoes `split_adjusted`, giving the prices actually traded at the time
+    pub fn split_unadjusted(&self, splits: &[Split]) -> Prices {
+        self.rescale_splits(splits, true)
+    }
+
+    fn rescale_splits(&self, splits: &[Split], undo: bool) -> Prices {
+        let mut prices = self.clone();
+        for split in splits {
+            let (timestamp, ratio) = match (date_to_timestamp(&split.date), split.ratio()) {
+                (Some(timestamp), Some(ratio)) if undo => (timestamp, 1.0 / ratio),
+                (Some(timestamp), Some(ratio)) => (timestamp, ratio),
+                _ => continue,
+            };
+            let before = prices
+                .timestamps
+                .iter()
+                .take_while(|t| **t < timestamp)
+                .count();
+            for i in 0..before.min(prices.len()) {
+                prices.open[i] /= ratio;
+                prices.high[i] /= ratio;
+                prices.low[i] /= ratio;
+                prices.close[i] /= ratio;
+                prices.volume[i] *= ratio;
+            }
+        }
+        prices
+    }
+
+    /// Return from the first close to the last in percent
+    pub fn change_percent(&self) -> Option<f64> {
+        let first = *self.close.first()?;
+        if first == 0.0 {
+            return None;
+        }
+        Some((self.last_close()? / first - 1.0) * 100.0)
+    }
+
+    /// Adds bars from a later fetch, replacing any that overlap
+    pub fn append(&mut self, newer: Prices) {
+        let first = match newer.timestamps.first() {
+            Some(first) => *first,
+            None => return,
+        };
+        let keep = self.timestamps.iter().take_while(|t| **t < first).count();
+        self.open.truncate(keep);
+        self.high.truncate(keep);
+        self.low.truncate(keep);
+        self.close.truncate(keep);
+        self.volume.truncate(keep);
+        self.timestamps.truncate(keep);
+
+        self.open.extend(newer.open);
+        self.high.extend(newer.high);
+        self.low.extend(newer.low);
+        self.close.extend(newer.close);
+        self.volume.extend(newer.volume);
+        self.timestamps.extend(newer.timestamps);
+        self.status = newer.status;
+    }
+
+    /// Folds a live trade into the last bar, or starts a new one if the trade
+    /// is past the end of its `period`. Trades from before the last bar are
+    /// ignored.
+    pub fn add_trade(&mut self, trade: &LiveData, period: BarPeriod) {
+        let time = (trade.time / 1000) as i64;
+        let last = self.timestamps.last().copied();
+        let start = match last {
+            Some(last) if time < last => return,
+            Some(last) if time < period.next(last) => {
+                let i = self.timestamps.len() - 1;
+                self.high[i] = self.high[i].max(trade.price);
+                self.low[i] = self.low[i].min(trade.price);
+                self.close[i] = trade.price;
+                self.volume[i] += trade.volume;
+                return;
+            }
+            _ => period.start(last, time),
+        };
+        self.open.push(trade.price);
+        self.high.push(trade.price);
+        self.low.push(trade.price);
+        self.close.push(trade.price);
+        self.volume.push(trade.volume);
+        self.timestamps.push(start);
+        self.status = String::from("ok");
+    }
+}
+
+/// Length of the bars live trades are folded into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarPeriod {
+    Seconds(i64),
+    /// Calendar weeks, starting on Monday
+    Week,
+    /// Calendar months
+    Month,
+}
+
+impl BarPeriod {
+    const WEEK_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+    /// Start of the bar after the one starting at `start`
+    fn next(&self, start: i64) -> i64 {
+        match self {
+            BarPeriod::Seconds(seconds) => start + (*seconds).max(1),
+            BarPeriod::Week => start + BarPeriod::WEEK_SECONDS,
+            BarPeriod::Month => {
+                let date = timestamp_to_date(start).date_naive();
+                let next = match date.month() {
+                    12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+                    month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1),
+                };
+                day_start(next.unwrap())
+            }
+        }
+    }
+
+    /// Start of the bar `time` falls in. Bars of a fixed length stay on the
+    /// grid of the `last` one.
+    fn start(&self, last: Option<i64>, time: i64) -> i64 {
+        let on_grid = |seconds: i64| match last {
+            Some(last) => last + (time - last) / seconds * seconds,
+            None => time - time.rem_euclid(seconds),
+        };
+        let date = timestamp_to_date(time).date_naive();
+        match self {
+            BarPeriod::Seconds(seconds) => on_grid((*seconds).max(1)),
+            BarPeriod::Week if last.is_some() => on_grid(BarPeriod::WEEK_SECONDS),
+            BarPeriod::Week => {
+                day_start(date - Duration::days(date.weekday().num_days_from_monday().into()))
+            }
+            BarPeriod::Month => day_start(date.with_day(1).unwrap()),
+        }
+    }
+}
+
+/// Time span shown on the price chart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartRange {
+    OneDay,
+    FiveDays,
+    OneMonth,
+    SixMonths,
+    YearToDate,
+    OneYear,
+    FiveYears,
+    Max,
+}
+
+impl ChartRange {
+    /// In the order they are bound to the number keys 1 to 8
+    pub const ALL: [ChartRange; 8] = [
+        ChartRange::OneDay,
+        ChartRange::FiveDays,
+        ChartRange::OneMonth,
+        ChartRange::SixMonths,
+        ChartRange::YearToDate,
+        ChartRange::OneYear,
+        ChartRange::FiveYears,
+        ChartRange::Max,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChartRange::OneDay => "1D",
+            ChartRange::FiveDays => "5D",
+            ChartRange::OneMonth => "1M",
+            ChartRange::SixMonths => "6M",
+            ChartRange::YearToDate => "YTD",
+            ChartRange::OneYear => "1Y",
+            ChartRange::FiveYears => "5Y",
+            ChartRange::Max => "Max",
+        }
+    }
+
+    /// Candle resolution understood by the candles endpoint: minutes, or D/W/M
+    pub fn resolution(&self) -> &'static str {
+        match self {
+            ChartRange::OneDay => "1",
+            ChartRange::FiveDays => "5",
+            ChartRange::OneMonth => "60",
+            ChartRange::SixMonths | ChartRange::YearToDate | ChartRange::OneYear => "D",
+            ChartRange::FiveYears => "W",
+            ChartRange::Max => "M",
+        }
+    }
+
+    /// Length of one bar at `resolution`
+    pub fn bar_period(&self) -> BarPeriod {
+        match self {
+            ChartRange::OneDay => BarPeriod::Seconds(60),
+            ChartRange::FiveDays => BarPeriod::Seconds(5 * 60),
+            ChartRange::OneMonth => BarPeriod::Seconds(60 * 60),
+            ChartRange::SixMonths | ChartRange::YearToDate | ChartRange::OneYear => {
+                BarPeriod::Seconds(24 * 60 * 60)
+            }
+            ChartRange::FiveYears => BarPeriod::Week,
+            ChartRange::Max => BarPeriod::Month,
+        }
+    }
+
+    pub fn is_intraday(&self) -> bool {
+        matches!(
+            self,
+            ChartRange::OneDay | ChartRange::FiveDays | ChartRange::OneMonth
+        )
+    }
+
+    /// Unix timestamps `(from, to)` of the range ending at `now`
+    pub fn window(&self, now: DateTime<Utc>) -> (i64, i64) {
+        let from = match self {
+            ChartRange::OneDay => now - Duration::days(1),
+            ChartRange::FiveDays => now - Duration::days(5),
+            ChartRange::OneMonth => now - Duration::days(30),
+            ChartRange::SixMonths => now - Duration::days(182),
+            ChartRange::YearToDate => Utc.with_ymd_and_hms(now.year(), 1, 1, 0, 0, 0).unwrap(),
+            ChartRange::OneYear => now - Duration::days(365),
+            ChartRange::FiveYears => now - Duration::days(5 * 365),
+            ChartRange::Max => timestamp_to_date(0),
+        };
+        (from.timestamp(), now.timestamp())
+    }
+}
+
+fn slice<T: Clone>(values: &[T], start: usize, end: usize) -> Vec<T> {
+    values
+        .get(start..end.min(values.len()))
+        .map_or_else(Vec::new, |values| values.to_vec())
+}
+
+pub fn timestamp_to_date(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).unwrap()
+}
+
+/// Unix timestamp of the start of `date`
+fn day_start(date: NaiveDate) -> i64 {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .timestamp()
+}
+
+/// Unix timestamp of the start of a `YYYY-MM-DD` day
+pub fn date_to_timestamp(date: &str) -> Option<i64> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| Utc.from_utc_datetime(&date).timestamp())
+}
+
+/// A security from the symbol list of an exchange, crypto and forex pairs
+/// included
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 pub struct Stock {
+    /// Not listed for crypto and forex pairs
+    #[serde(default)]
     pub currency: String,
     pub description: String,
-    #[serde(rename(deserialize = "displaySymbol"))]
+    #[serde(rename = "displaySymbol")]
     pub display_symbol: String,
     pub symbol: String,
-    #[serde(rename(deserialize = "type"))]
+    /// Not listed for crypto and forex pairs
+    #[serde(rename = "type", default)]
     pub security_type: String,
+    /// Code of the exchange the symbol list came from, e.g. `US` or `L` for London
+    #[serde(default)]
+    pub exchange: String,
+    #[serde(default)]
+    pub asset_class: AssetClass,
 }
 
 impl PartialEq for Stock {
@@ -71,123 +967,445 @@ impl PartialEq for Stock {
     }
 }
 
-pub fn get_error_company() -> CompanyInfo {
-    CompanyInfo {
-        name: String::from("Error getting comapny info"),
-        ticker: String::from("PLC"),
-        country: String::from("UK"),
-        market_capitalization: 100.0,
-        exchange: String::from("LSE"),
-        currency: String::from("USD"),
-        industry: String::from("Technology"),
-        prices: Prices {
-            close: vec![1.0, 2.0, 3.0],
-            live_price: 1.0,
-            movement_indicator: String::new(),
-        },
-    }
-}
-
-#[tokio::main]
-pub async fn get_equity(api_key: &str, symbol: &str) -> Result<CompanyInfo, Box<dyn Error>> {
-    let url = format!("https://finnhub.io/api/v1/stock/profile2?symbol={}", symbol);
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(&url)
-        .header("X-Finnhub-Token", api_key)
-        .send()
-        .await?;
-    info!("{}", resp.status());
-
-    if resp.status().is_success() {
-        let mut company: CompanyInfo = resp.json().await?;
-        company.market_capitalization *= 1000000.0;
-        info!("{:#?}", company);
-        Ok(company)
-    } else {
-        Err("Error getting company info...".into())
-    }
-}
-
-#[tokio::main]
-pub async fn get_all_securites(api_key: &str) -> Result<Vec<Stock>, Box<dyn std::error::Error>> {
-    let url = "https://finnhub.io/api/v1/stock/symbol?exchange=US";
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(url)
-        .header("X-Finnhub-Token", api_key)
-        .send()
-        .await?;
-
-    if resp.status().is_success() {
-        let securities: Vec<Stock> = resp.json().await?;
-        info!("Prices: {:#?}", securities);
-        Ok(securities)
-    } else {
-        // TODO: fix error handling here
-        panic!("Cannot get list of securities")
-    }
-}
-
-#[tokio::main]
-pub async fn get_price_history(
-    api_key: &str,
-    symbol: &str,
-    resolution: &str,
-) -> Result<Prices, Box<dyn std::error::Error>> {
-    let now = SystemTime::now();
-    let now: DateTime<Utc> = now.into();
-    let one_year_ago = now - Duration::days(365);
-    let now = now.format("%s").to_string();
-    let one_year_ago = one_year_ago.format("%s").to_string();
-
-    info!("Unix timestamp: {}", now);
-    info!("Unix - 1 year: {}", one_year_ago);
-    let url = format!(
-        "https://finnhub.io/api/v1/stock/candle?symbol={}&resolution={}&from={}&to={}",
-        symbol, resolution, one_year_ago, now
-    );
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(&url)
-        .header("X-Finnhub-Token", api_key)
-        .send()
-        .await?;
-    info!("Prices status {}", resp.status());
-    info!("Price response: {:#?}", resp);
-
-    if resp.status().is_success() {
-        info!("price success");
-        let prices: Prices = resp.json().await?;
-        info!("Prices: {:#?}", prices.close.len());
-        Ok(prices)
-    } else {
-        // TODO: fix error handling here, do not return ok if not ok
-        Ok(Prices {
-            close: vec![0.0],
-            live_price: 1.0,
-            movement_indicator: String::new(),
-        })
+impl Eq for Stock {}
+
+impl Hash for Stock {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.symbol.hash(state);
     }
 }
 
-// TODO: Create type alias.
-pub fn live_price(
-    mut socket: WebSocket<Stream<TcpStream, TlsStream<TcpStream>>>,
-    tx: Sender<(String, f64)>,
-) {
-    loop {
-        let msg = socket.read_message().expect("Error reading message");
-        //        println!("{}",msg);
-        if let Message::Text(text) = msg {
-            let msg: Feed = serde_json::from_str(&text).unwrap();
-            if let Some(data) = msg.data {
-                let price: f64 = data[0].price as f64;
-                let symbol = data[0].symbol.to_string();
-                //debug!("ws price {}: {}", symbol, price);
-                tx.send((symbol, price))
-                    .expect("Error sending ws data between threads");
-            }
+impl PartialOrd for Stock {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Stock {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.symbol.cmp(&other.symbol)
+    }
+}
+
+/// A source of market data. The UI only talks to this trait, so finnhub.io can
+/// be swapped for another backend or a test double.
+#[async_trait]
+pub trait MarketDataProvider: Send + Sync {
+    /// Company profile for a single symbol
+    async fn company_profile(&self, symbol: &str) -> Result<CompanyInfo, StonksError>;
+
+    /// Codes of the exchanges listed for `asset_class`. Stock exchanges are
+    /// not listed by finnhub.io.
+    async fn exchanges(&self, asset_class: AssetClass) -> Result<Vec<String>, StonksError>;
+
+    /// Every security of `asset_class` the provider can quote on `exchange`
+    async fn securities(
+        &self,
+        asset_class: AssetClass,
+        exchange: &str,
+    ) -> Result<Vec<Stock>, StonksError>;
+
+    /// Latest price and day range of a stock
+    async fn quote(&self, symbol: &str) -> Result<Quote, StonksError>;
+
+    /// Headlines about a company between the dates `from` and `to`, formatted
+    /// as `YYYY-MM-DD`
+    async fn company_news(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<NewsArticle>, StonksError>;
+
+    /// Latest headlines of a news category, e.g. `crypto` or `forex`
+    async fn market_news(&self, category: &str) -> Result<Vec<NewsArticle>, StonksError>;
+
+    /// Earnings reports of a company between the dates `from` and `to`,
+    /// formatted as `YYYY-MM-DD`
+    async fn earnings_calendar(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<EarningsRelease>, StonksError>;
+
+    /// Actual against estimated EPS of the last reported quarters
+    async fn earnings_surprises(&self, symbol: &str) -> Result<Vec<EarningsSurprise>, StonksError>;
+
+    /// Key metrics of a company and their history
+    async fn fundamentals(&self, symbol: &str) -> Result<Fundamentals, StonksError>;
+
+    /// Monthly counts of analyst ratings of a stock
+    async fn recommendations(&self, symbol: &str) -> Result<Vec<RecommendationTrend>, StonksError>;
+
+    /// Highest, lowest and average analyst price target of a stock
+    async fn price_target(&self, symbol: &str) -> Result<PriceTarget, StonksError>;
+
+    /// Symbols of companies in the same country and industry as `symbol`
+    async fn peers(&self, symbol: &str) -> Result<Vec<String>, StonksError>;
+
+    /// Dividends of a stock with an ex-date between `from` and `to`,
+    /// formatted as `YYYY-MM-DD`
+    async fn dividends(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Dividend>, StonksError>;
+
+    /// Splits of a stock between the dates `from` and `to`
+    async fn splits(&self, symbol: &str, from: &str, to: &str) -> Result<Vec<Split>, StonksError>;
+
+    /// Insider transactions in a stock between the dates `from` and `to`
+    async fn insider_transactions(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<InsiderTransaction>, StonksError>;
+
+    /// Exchange rates of every currency against `base`
+    async fn forex_rates(&self, base: &str) -> Result<ForexRates, StonksError>;
+
+    /// Price candles at `resolution` between the unix timestamps `from` and `to`
+    async fn price_history(
+        &self,
+        asset_class: AssetClass,
+        symbol: &str,
+        resolution: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Prices, StonksError>;
+
+    /// Streams live trades into `tx` for the symbols subscribed to through
+    /// `commands`, along with the state of the connection. Blocks for as long
+    /// as the feed is running, so it gets a thread of its own.
+    fn live_feed(
+        &self,
+        commands: Receiver<FeedCommand>,
+        tx: Sender<FeedEvent>,
+    ) -> Result<(), StonksError>;
+
+    /// API calls left before requests start queueing, if the provider has a
+    /// quota
+    fn quota(&self) -> Option<Quota> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(timestamps: &[i64], close: &[f64]) -> Prices {
+        Prices {
+            open: close.to_vec(),
+            high: close.to_vec(),
+            low: close.to_vec(),
+            close: close.to_vec(),
+            volume: vec![1.0; close.len()],
+            timestamps: timestamps.to_vec(),
+            status: String::from("ok"),
+            ..Prices::default()
+        }
+    }
+
+    fn trade(seconds: i64, price: f64) -> LiveData {
+        LiveData {
+            price,
+            symbol: String::from("AAPL"),
+            time: seconds as u64 * 1000,
+            volume: 2.0,
         }
     }
+
+    #[test]
+    fn trades_fold_into_the_last_bar() {
+        let mut prices = bars(&[600], &[10.0]);
+        prices.add_trade(&trade(630, 12.0), BarPeriod::Seconds(60));
+        prices.add_trade(&trade(659, 9.0), BarPeriod::Seconds(60));
+        assert_eq!(prices.timestamps, vec![600]);
+        assert_eq!(prices.high, vec![12.0]);
+        assert_eq!(prices.low, vec![9.0]);
+        assert_eq!(prices.close, vec![9.0]);
+        assert_eq!(prices.volume, vec![5.0]);
+    }
+
+    #[test]
+    fn trades_roll_over_at_the_bar_boundary() {
+        let mut prices = bars(&[600], &[10.0]);
+        prices.add_trade(&trade(660, 11.0), BarPeriod::Seconds(60));
+        assert_eq!(prices.timestamps, vec![600, 660]);
+        assert_eq!(prices.open, vec![10.0, 11.0]);
+        assert_eq!(prices.volume, vec![1.0, 2.0]);
+        // A gap of several bars stays on the grid
+        prices.add_trade(&trade(845, 12.0), BarPeriod::Seconds(60));
+        assert_eq!(prices.timestamps, vec![600, 660, 840]);
+    }
+
+    #[test]
+    fn old_trades_are_ignored() {
+        let mut prices = bars(&[600], &[10.0]);
+        prices.add_trade(&trade(599, 1.0), BarPeriod::Seconds(60));
+        assert_eq!(prices.timestamps, vec![600]);
+        assert_eq!(prices.low, vec![10.0]);
+    }
+
+    #[test]
+    fn first_trade_starts_an_aligned_bar() {
+        let mut prices = Prices {
+            status: String::from("no_data"),
+            ..Prices::default()
+        };
+        prices.add_trade(&trade(1000, 5.0), BarPeriod::Seconds(300));
+        assert_eq!(prices.timestamps, vec![900]);
+        assert_eq!(prices.close, vec![5.0]);
+        assert_eq!(prices.status, "ok");
+    }
+
+    #[test]
+    fn monthly_bars_follow_the_calendar() {
+        let day = |date: &str| date_to_timestamp(date).unwrap();
+        let mut prices = bars(&[day("2020-10-01")], &[10.0]);
+        prices.add_trade(&trade(day("2020-10-31") + 3600, 11.0), BarPeriod::Month);
+        assert_eq!(prices.timestamps, vec![day("2020-10-01")]);
+        prices.add_trade(&trade(day("2020-11-01") + 60, 12.0), BarPeriod::Month);
+        assert_eq!(
+            prices.timestamps,
+            vec![day("2020-10-01"), day("2020-11-01")]
+        );
+
+        // February is shorter than 30 days, March starts a bar of its own
+        let mut prices = bars(&[day("2021-02-01")], &[10.0]);
+        prices.add_trade(&trade(day("2021-03-01"), 11.0), BarPeriod::Month);
+        assert_eq!(
+            prices.timestamps,
+            vec![day("2021-02-01"), day("2021-03-01")]
+        );
+        // Months without trades are skipped, and the year rolls over
+        prices.add_trade(&trade(day("2022-01-15"), 12.0), BarPeriod::Month);
+        assert_eq!(prices.timestamps.last(), Some(&day("2022-01-01")));
+    }
+
+    #[test]
+    fn weekly_bars_start_on_monday() {
+        let day = |date: &str| date_to_timestamp(date).unwrap();
+        let mut prices = Prices::default();
+        // A Thursday
+        prices.add_trade(&trade(day("2020-10-01") + 3600, 10.0), BarPeriod::Week);
+        assert_eq!(prices.timestamps, vec![day("2020-09-28")]);
+        prices.add_trade(&trade(day("2020-10-04"), 11.0), BarPeriod::Week);
+        prices.add_trade(&trade(day("2020-10-05"), 12.0), BarPeriod::Week);
+        assert_eq!(
+            prices.timestamps,
+            vec![day("2020-09-28"), day("2020-10-05")]
+        );
+    }
+
+    #[test]
+    fn append_replaces_overlapping_bars() {
+        let mut prices = bars(&[0, 60, 120], &[1.0, 2.0, 3.0]);
+        prices.append(bars(&[60, 120, 180], &[20.0, 30.0, 40.0]));
+        assert_eq!(prices.timestamps, vec![0, 60, 120, 180]);
+        assert_eq!(prices.close, vec![1.0, 20.0, 30.0, 40.0]);
+        assert_eq!(prices.volume.len(), 4);
+    }
+
+    #[test]
+    fn append_nothing_keeps_the_bars() {
+        let mut prices = bars(&[0, 60], &[1.0, 2.0]);
+        prices.append(Prices {
+            status: String::from("no_data"),
+            ..Prices::default()
+        });
+        assert_eq!(prices.close, vec![1.0, 2.0]);
+        assert_eq!(prices.status, "ok");
+    }
+
+    #[test]
+    fn between_is_inclusive() {
+        let prices = bars(&[0, 60, 120, 180], &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(prices.between(60, 120).close, vec![2.0, 3.0]);
+        assert_eq!(prices.between(30, 150).timestamps, vec![60, 120]);
+        assert!(prices.between(200, 300).is_empty());
+        assert!(prices.between(120, 60).is_empty());
+    }
+
+    fn split(date: &str, from_factor: f64, to_factor: f64) -> Split {
+        Split {
+            date: date.to_string(),
+            from_factor,
+            to_factor,
+        }
+    }
+
+    #[test]
+    fn split_adjusting_scales_the_bars_before_each_split() {
+        let day = |date: &str| date_to_timestamp(date).unwrap();
+        let traded = bars(
+            &[day("2020-08-28"), day("2020-08-31"), day("2021-01-04")],
+            &[400.0, 100.0, 120.0],
+        );
+        // A 4:1 split, then a made up 1:2 reverse split
+        let splits = [split("2020-08-31", 1.0, 4.0), split("2021-01-04", 2.0, 1.0)];
+
+        let adjusted = traded.split_adjusted(&splits);
+        assert_eq!(adjusted.close, vec![200.0, 200.0, 120.0]);
+        assert_eq!(adjusted.high, vec![200.0, 200.0, 120.0]);
+        assert_eq!(adjusted.volume, vec![2.0, 0.5, 1.0]);
+        assert_eq!(adjusted.timestamps, traded.timestamps);
+
+        let unadjusted = adjusted.split_unadjusted(&splits);
+        assert_eq!(unadjusted.close, traded.close);
+        assert_eq!(unadjusted.open, traded.open);
+        assert_eq!(unadjusted.low, traded.low);
+        assert_eq!(unadjusted.volume, traded.volume);
+    }
+
+    #[test]
+    fn unusable_splits_are_skipped() {
+        let traded = bars(&[0, 86400], &[10.0, 10.0]);
+        let splits = [split("someday", 1.0, 2.0), split("1970-01-02", 0.0, 2.0)];
+        assert_eq!(traded.split_adjusted(&splits).close, vec![10.0, 10.0]);
+        assert_eq!(traded.split_adjusted(&[]).close, vec![10.0, 10.0]);
+    }
+
+    fn insider(date: &str, code: &str, change: f64, price: Option<f64>) -> InsiderTransaction {
+        InsiderTransaction {
+            name: String::from("Jane Doe"),
+            change,
+            transaction_date: date.to_string(),
+            transaction_code: code.to_string(),
+            transaction_price: price,
+            ..InsiderTransaction::default()
+        }
+    }
+
+    #[test]
+    fn net_insider_buying_since_a_date() {
+        let transactions = [
+            insider("2020-01-10", "P", 100.0, Some(10.0)),
+            insider("2020-03-01", "S", -300.0, Some(20.0)),
+            insider("2020-06-15", "P", 50.0, Some(30.0)),
+            // Grants and exercises are not trades
+            insider("2020-06-20", "A", 1000.0, None),
+            insider("2020-06-21", "M", 500.0, Some(1.0)),
+        ];
+        assert_eq!(
+            net_insider_buying(&transactions, "2020-01-01"),
+            (-150.0, -3500.0)
+        );
+        // The cutoff day itself is included
+        assert_eq!(
+            net_insider_buying(&transactions, "2020-03-01"),
+            (-250.0, -4500.0)
+        );
+        assert_eq!(
+            net_insider_buying(&transactions, "2020-04-01"),
+            (50.0, 1500.0)
+        );
+        assert_eq!(net_insider_buying(&transactions, "2021-01-01"), (0.0, 0.0));
+        // Without a price the shares still count
+        let unpriced = [insider("2020-05-01", "S", -10.0, None)];
+        assert_eq!(net_insider_buying(&unpriced, "2020-01-01"), (-10.0, 0.0));
+    }
+
+    fn daily_bars() -> Prices {
+        Prices {
+            open: vec![10.0, 11.0, 12.0],
+            high: vec![12.0, 15.0, 13.0],
+            low: vec![9.0, 8.0, 11.0],
+            close: vec![11.0, 12.0, 12.5],
+            volume: vec![1.0; 3],
+            timestamps: vec![0, 86400, 172800],
+            status: String::from("ok"),
+            ..Prices::default()
+        }
+    }
+
+    #[test]
+    fn quote_from_the_last_daily_bar() {
+        let quote = Quote::from_candles(&daily_bars()).unwrap();
+        assert_eq!(quote.current, 12.5);
+        assert_eq!(quote.open, 12.0);
+        assert_eq!((quote.low, quote.high), (11.0, 13.0));
+        assert_eq!(quote.previous_close, 12.0);
+        assert_eq!(quote.timestamp, 172800);
+
+        // A single bar closes against its own open
+        let quote = Quote::from_candles(&bars(&[0], &[5.0])).unwrap();
+        assert_eq!(quote.previous_close, 5.0);
+        assert_eq!(Quote::from_candles(&Prices::default()), None);
+    }
+
+    #[test]
+    fn year_range_spans_the_daily_bars_and_today() {
+        let mut quote = Quote {
+            current: 16.0,
+            high: 16.0,
+            low: 14.0,
+            ..Quote::default()
+        };
+        quote.set_year_range(&daily_bars());
+        assert_eq!(quote.year_range, Some((8.0, 16.0)));
+
+        let mut quote = Quote::default();
+        quote.set_year_range(&daily_bars());
+        assert_eq!(quote.year_range, Some((8.0, 15.0)));
+
+        // No bars leave the range unknown
+        quote.year_range = None;
+        quote.set_year_range(&Prices::default());
+        assert_eq!(quote.year_range, None);
+    }
+
+    fn rates() -> ForexRates {
+        ForexRates {
+            base: String::from("USD"),
+            quote: vec![(String::from("EUR"), 0.8), (String::from("JPY"), 100.0)]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn cross_rates() {
+        let rates = rates();
+        assert_eq!(rates.rate("USD", "EUR"), Some(0.8));
+        assert_eq!(rates.rate("EUR", "USD"), Some(1.25));
+        assert_eq!(rates.rate("EUR", "JPY"), Some(125.0));
+        assert_eq!(rates.rate("USD", "GBP"), None);
+    }
+
+    #[test]
+    fn update_inverts_a_rate_into_the_base() {
+        let mut rates = rates();
+        // 1 EUR buys 1.6 USD, so 1 USD buys 0.625 EUR
+        rates.update("EUR", "USD", 1.6);
+        assert_eq!(rates.quote["EUR"], 0.625);
+        assert_eq!(rates.rate("EUR", "USD"), Some(1.6));
+        rates.update("USD", "JPY", 150.0);
+        assert_eq!(rates.quote["JPY"], 150.0);
+    }
+
+    #[test]
+    fn update_adds_a_currency_through_a_known_one() {
+        let mut rates = rates();
+        // 1 GBP buys 1.5 EUR, which is 1.5 / 0.8 USD
+        rates.update("GBP", "EUR", 1.5);
+        assert_eq!(rates.rate("GBP", "EUR"), Some(1.5));
+        rates.update("EUR", "CHF", 0.5);
+        assert_eq!(rates.quote["CHF"], 0.4);
+    }
+
+    #[test]
+    fn update_ignores_unknown_pairs_and_bad_rates() {
+        let mut rates = rates();
+        rates.update("GBP", "CHF", 1.1);
+        rates.update("EUR", "USD", 0.0);
+        assert_eq!(rates, self::rates());
+        // The base always stays at one
+        rates.update("USD", "EUR", 0.9);
+        assert_eq!(rates.units("USD"), Some(1.0));
+    }
 }
@@ -0,0 +1,679 @@
+use crate::asset::{
+    timestamp_to_date, AssetClass, ChartRange, CompanyInfo, Dividend, EarningsRelease,
+    EarningsSurprise, ForexRates, Fundamentals, InsiderTransaction, MarketDataProvider,
+    NewsArticle, PriceTarget, Prices, Quote, RecommendationTrend, Split, Stock,
+};
+use crate::error::StonksError;
+use crate::scheduler::Quota;
+use crate::subscription::{FeedCommand, FeedEvent};
+use async_trait::async_trait;
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use mpsc::{Receiver, Sender};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    env, fs,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+
+const SYMBOLS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const NEWS_TTL: Duration = Duration::from_secs(15 * 60);
+const EARNINGS_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+const FUNDAMENTALS_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+const ANALYSTS_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+const PEERS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const ACTIONS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const INSIDERS_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+const QUOTE_TTL: Duration = Duration::from_secs(15);
+const RATES_TTL: Duration = Duration::from_secs(60);
+const PROFILE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Keeps responses from another provider on disk so they survive restarts.
+/// Symbol lists and profiles are reused until they expire. Candles are kept
+/// forever and only bars newer than the last cached one are fetched, unless
+/// the stock split since and the older bars need adjusting.
+pub struct CachedProvider<P: MarketDataProvider> {
+    inner: P,
+    dir: PathBuf,
+}
+
+/// Candles on disk, the start of the window they were fetched for and when
+#[derive(Serialize, Deserialize)]
+struct CachedCandles {
+    from: i64,
+    /// Unix timestamp of the last fetch, zero for entries from before it was
+    /// kept
+    #[serde(default)]
+    fetched: i64,
+    prices: Prices,
+}
+
+impl<P: MarketDataProvider> CachedProvider<P> {
+    pub fn new<D: Into<PathBuf>>(inner: P, dir: D) -> CachedProvider<P> {
+        CachedProvider {
+            inner,
+            dir: dir.into(),
+        }
+    }
+
+    /// Cached value at `path` and whether it is younger than `ttl`
+    fn read<T: DeserializeOwned>(&self, path: &str, ttl: Duration) -> Option<(T, bool)> {
+        let path = self.dir.join(path);
+        let text = fs::read_to_string(&path).ok()?;
+        let value = match serde_json::from_str(&text) {
+            Ok(value) => value,
+            Err(err) => {
+                warn!("Ignoring corrupt cache file {}: {}", path.display(), err);
+                return None;
+            }
+        };
+        let fresh = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age < ttl);
+        Some((value, fresh))
+    }
+
+    /// Failing to write the cache is logged but never fails the request
+    fn write<T: Serialize>(&self, path: &str, value: &T) {
+        let path = self.dir.join(path);
+        let res = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .map_err(StonksError::from)
+            .and_then(|_| Ok(serde_json::to_string(value)?))
+            .and_then(|text| Ok(fs::write(&path, text)?));
+        if let Err(err) = res {
+            warn!("Could not write cache file {}: {}", path.display(), err);
+        }
+    }
+
+    /// Serves a fresh cache entry, otherwise fetches and stores a new one.
+    /// Falls back to an expired entry if the fetch fails.
+    async fn cached<T, F>(&self, path: &str, ttl: Duration, fetch: F) -> Result<T, StonksError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<T, StonksError>>,
+    {
+        let cached = self.read::<T>(path, ttl);
+        if let Some((value, true)) = cached {
+            debug!("Cache hit {}", path);
+            return Ok(value);
+        }
+        match fetch.await {
+            Ok(value) => {
+                self.write(path, &value);
+                Ok(value)
+            }
+            Err(err) => match cached {
+                Some((value, _)) => {
+                    warn!("Serving expired cache for {}: {}", path, err);
+                    Ok(value)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Splits of `symbol` dated after the day `fetched`. Bars at daily or
+    /// longer resolutions fetched back then are not adjusted for them.
+    async fn splits_since(
+        &self,
+        asset_class: AssetClass,
+        symbol: &str,
+        resolution: &str,
+        fetched: i64,
+    ) -> Vec<Split> {
+        if asset_class != AssetClass::Stock || !matches!(resolution, "D" | "W" | "M") {
+            return Vec::new();
+        }
+        // The same window as the corporate actions, so they share a cache entry
+        let (from, to) = ChartRange::Max.window(Utc::now());
+        let from = timestamp_to_date(from).format("%Y-%m-%d").to_string();
+        let to = timestamp_to_date(to).format("%Y-%m-%d").to_string();
+        let since = timestamp_to_date(fetched).format("%Y-%m-%d").to_string();
+        match self.splits(symbol, &from, &to).await {
+            Ok(splits) => splits
+                .into_iter()
+                .filter(|split| split.date > since)
+                .collect(),
+            Err(err) => {
+                debug!("No splits for {}: {}", symbol, err);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<P: MarketDataProvider> MarketDataProvider for CachedProvider<P> {
+    async fn company_profile(&self, symbol: &str) -> Result<CompanyInfo, StonksError> {
+        let path = format!("profile/{}.json", file_name(symbol));
+        self.cached(&path, PROFILE_TTL, self.inner.company_profile(symbol))
+            .await
+    }
+
+    async fn exchanges(&self, asset_class: AssetClass) -> Result<Vec<String>, StonksError> {
+        let path = format!("exchanges/{}.json", asset_class.path());
+        self.cached(&path, SYMBOLS_TTL, self.inner.exchanges(asset_class))
+            .await
+    }
+
+    async fn securities(
+        &self,
+        asset_class: AssetClass,
+        exchange: &str,
+    ) -> Result<Vec<Stock>, StonksError> {
+        let path = format!(
+            "symbols/{}_{}.json",
+            asset_class.path(),
+            file_name(exchange)
+        );
+        self.cached(
+            &path,
+            SYMBOLS_TTL,
+            self.inner.securities(asset_class, exchange),
+        )
+        .await
+    }
+
+    /// An expired quote would pass for today's, so it is never served when
+    /// the fetch fails
+    async fn quote(&self, symbol: &str) -> Result<Quote, StonksError> {
+        let path = format!("quote/{}.json", file_name(symbol));
+        if let Some((quote, true)) = self.read(&path, QUOTE_TTL) {
+            debug!("Cache hit {}", path);
+            return Ok(quote);
+        }
+        let quote = self.inner.quote(symbol).await?;
+        self.write(&path, &quote);
+        Ok(quote)
+    }
+
+    async fn company_news(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<NewsArticle>, StonksError> {
+        let path = format!("news/{}.json", file_name(symbol));
+        self.cached(&path, NEWS_TTL, self.inner.company_news(symbol, from, to))
+            .await
+    }
+
+    async fn market_news(&self, category: &str) -> Result<Vec<NewsArticle>, StonksError> {
+        let path = format!("news/category_{}.json", file_name(category));
+        self.cached(&path, NEWS_TTL, self.inner.market_news(category))
+            .await
+    }
+
+    /// Keyed on the symbol alone, so every caller has to ask for the same
+    /// window. It moves along with the day.
+    async fn earnings_calendar(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<EarningsRelease>, StonksError> {
+        let path = format!("earnings/calendar_{}.json", file_name(symbol));
+        self.cached(
+            &path,
+            EARNINGS_TTL,
+            self.inner.earnings_calendar(symbol, from, to),
+        )
+        .await
+    }
+
+    async fn earnings_surprises(&self, symbol: &str) -> Result<Vec<EarningsSurprise>, StonksError> {
+        let path = format!("earnings/surprises_{}.json", file_name(symbol));
+        self.cached(&path, EARNINGS_TTL, self.inner.earnings_surprises(symbol))
+            .await
+    }
+
+    async fn fundamentals(&self, symbol: &str) -> Result<Fundamentals, StonksError> {
+        let path = format!("metric/{}.json", file_name(symbol));
+        self.cached(&path, FUNDAMENTALS_TTL, self.inner.fundamentals(symbol))
+            .await
+    }
+
+    async fn recommendations(&self, symbol: &str) -> Result<Vec<RecommendationTrend>, StonksError> {
+        let path = format!("analysts/recommendations_{}.json", file_name(symbol));
+        self.cached(&path, ANALYSTS_TTL, self.inner.recommendations(symbol))
+            .await
+    }
+
+    async fn price_target(&self, symbol: &str) -> Result<PriceTarget, StonksError> {
+        let path = format!("analysts/target_{}.json", file_name(symbol));
+        self.cached(&path, ANALYSTS_TTL, self.inner.price_target(symbol))
+            .await
+    }
+
+    async fn peers(&self, symbol: &str) -> Result<Vec<String>, StonksError> {
+        let path = format!("peers/{}.json", file_name(symbol));
+        self.cached(&path, PEERS_TTL, self.inner.peers(symbol))
+            .await
+    }
+
+    async fn dividends(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Dividend>, StonksError> {
+        let path = format!("dividends/{}.json", file_name(symbol));
+        self.cached(&path, ACTIONS_TTL, self.inner.dividends(symbol, from, to))
+            .await
+    }
+
+    async fn splits(&self, symbol: &str, from: &str, to: &str) -> Result<Vec<Split>, StonksError> {
+        let path = format!("splits/{}.json", file_name(symbol));
+        self.cached(&path, ACTIONS_TTL, self.inner.splits(symbol, from, to))
+            .await
+    }
+
+    async fn insider_transactions(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<InsiderTransaction>, StonksError> {
+        let path = format!("insiders/{}.json", file_name(symbol));
+        self.cached(
+            &path,
+            INSIDERS_TTL,
+            self.inner.insider_transactions(symbol, from, to),
+        )
+        .await
+    }
+
+    async fn forex_rates(&self, base: &str) -> Result<ForexRates, StonksError> {
+        let path = format!("forex/rates_{}.json", file_name(base));
+        self.cached(&path, RATES_TTL, self.inner.forex_rates(base))
+            .await
+    }
+
+    async fn price_history(
+        &self,
+        asset_class: AssetClass,
+        symbol: &str,
+        resolution: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Prices, StonksError> {
+        let path = format!("candle/{}_{}.json", file_name(symbol), resolution);
+        let cached = self
+            .read::<CachedCandles>(&path, candle_ttl(resolution))
+            .filter(|(cached, _)| cached.from <= from && !cached.prices.is_empty());
+
+        let fetched = Utc::now().timestamp();
+        let candles = match cached {
+            Some((cached, true)) => {
+                debug!("Cache hit {}", path);
+                return Ok(cached.prices.between(from, to));
+            }
+            Some((cached, false)) => {
+                let splits = self
+                    .splits_since(asset_class, symbol, resolution, cached.fetched)
+                    .await;
+                let refresh_from = if splits.is_empty() {
+                    // The last bar may have been incomplete, fetch it again too
+                    *cached.prices.timestamps.last().unwrap()
+                } else {
+                    info!(
+                        "{} split since {} was cached, fetching it again",
+                        symbol, path
+                    );
+                    cached.from
+                };
+                match self
+                    .inner
+                    .price_history(asset_class, symbol, resolution, refresh_from, to)
+                    .await
+                {
+                    Ok(newer) if splits.is_empty() => {
+                        info!("Fetched {} new bars for {}", newer.len(), path);
+                        let mut prices = cached.prices;
+                        prices.append(newer);
+                        CachedCandles {
+                            from: cached.from,
+                            fetched,
+                            prices,
+                        }
+                    }
+                    Ok(prices) => CachedCandles {
+                        from: cached.from,
+                        fetched,
+                        prices,
+                    },
+                    Err(err) => {
+                        warn!("Serving expired cache for {}: {}", path, err);
+                        // Close enough until the adjusted bars can be fetched
+                        let prices = cached.prices.split_adjusted(&splits);
+                        return Ok(prices.between(from, to));
+                    }
+                }
+            }
+            None => CachedCandles {
+                from,
+                fetched,
+                prices: self
+                    .inner
+                    .price_history(asset_class, symbol, resolution, from, to)
+                    .await?,
+            },
+        };
+        if !candles.prices.is_empty() {
+            self.write(&path, &candles);
+        }
+        Ok(candles.prices.between(from, to))
+    }
+
+    fn live_feed(
+        &self,
+        commands: Receiver<FeedCommand>,
+        tx: Sender<FeedEvent>,
+    ) -> Result<(), StonksError> {
+        self.inner.live_feed(commands, tx)
+    }
+
+    fn quota(&self) -> Option<Quota> {
+        self.inner.quota()
+    }
+}
+
+/// How long until the newest cached bar at `resolution` is worth refreshing
+fn candle_ttl(resolution: &str) -> Duration {
+    match resolution {
+        "D" => Duration::from_secs(60 * 60),
+        "W" | "M" => Duration::from_secs(24 * 60 * 60),
+        _ => Duration::from_secs(60),
+    }
+}
+
+/// Symbols like `BINANCE:BTCUSDT` are not valid file names everywhere
+pub fn file_name(symbol: &str) -> String {
+    symbol
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// `$XDG_CACHE_HOME/stonks`, falling back to `~/.cache/stonks`
+pub fn default_cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|dir| dir.join("stonks"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::date_to_timestamp;
+    use std::sync::Mutex;
+
+    /// Serves `bars` for any window and keeps track of the windows asked for
+    #[derive(Default)]
+    struct FakeProvider {
+        bars: Prices,
+        splits: Vec<Split>,
+        offline: bool,
+        requests: Mutex<Vec<(i64, i64)>>,
+    }
+
+    #[async_trait]
+    impl MarketDataProvider for FakeProvider {
+        async fn company_profile(&self, _: &str) -> Result<CompanyInfo, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn exchanges(&self, _: AssetClass) -> Result<Vec<String>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn securities(&self, _: AssetClass, _: &str) -> Result<Vec<Stock>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn quote(&self, _: &str) -> Result<Quote, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn company_news(
+            &self,
+            _: &str,
+            _: &str,
+            _: &str,
+        ) -> Result<Vec<NewsArticle>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn market_news(&self, _: &str) -> Result<Vec<NewsArticle>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn earnings_calendar(
+            &self,
+            _: &str,
+            _: &str,
+            _: &str,
+        ) -> Result<Vec<EarningsRelease>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn earnings_surprises(&self, _: &str) -> Result<Vec<EarningsSurprise>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn fundamentals(&self, _: &str) -> Result<Fundamentals, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn recommendations(&self, _: &str) -> Result<Vec<RecommendationTrend>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn price_target(&self, _: &str) -> Result<PriceTarget, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn peers(&self, _: &str) -> Result<Vec<String>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn dividends(&self, _: &str, _: &str, _: &str) -> Result<Vec<Dividend>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn splits(&self, _: &str, _: &str, _: &str) -> Result<Vec<Split>, StonksError> {
+            Ok(self.splits.clone())
+        }
+        async fn insider_transactions(
+            &self,
+            _: &str,
+            _: &str,
+            _: &str,
+        ) -> Result<Vec<InsiderTransaction>, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn forex_rates(&self, _: &str) -> Result<ForexRates, StonksError> {
+            Err(StonksError::NoData)
+        }
+        async fn price_history(
+            &self,
+            _: AssetClass,
+            _: &str,
+            _: &str,
+            from: i64,
+            to: i64,
+        ) -> Result<Prices, StonksError> {
+            self.requests.lock().unwrap().push((from, to));
+            if self.offline {
+                return Err(StonksError::Io(String::from("offline")));
+            }
+            Ok(self.bars.between(from, to))
+        }
+        fn live_feed(
+            &self,
+            _: Receiver<FeedCommand>,
+            _: Sender<FeedEvent>,
+        ) -> Result<(), StonksError> {
+            Ok(())
+        }
+    }
+
+    fn bars(timestamps: &[i64], close: &[f64]) -> Prices {
+        Prices {
+            open: close.to_vec(),
+            high: close.to_vec(),
+            low: close.to_vec(),
+            close: close.to_vec(),
+            volume: vec![1.0; close.len()],
+            timestamps: timestamps.to_vec(),
+            status: String::from("ok"),
+            ..Prices::default()
+        }
+    }
+
+    fn provider(name: &str, inner: FakeProvider) -> CachedProvider<FakeProvider> {
+        let dir = env::temp_dir().join(format!("stonks-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        CachedProvider::new(inner, dir)
+    }
+
+    /// Writes daily AAPL candles fetched at `fetched` that have expired since
+    fn cache_expired(cache: &CachedProvider<FakeProvider>, fetched: i64, prices: Prices) {
+        let path = "candle/AAPL_D.json";
+        let candles = CachedCandles {
+            from: 0,
+            fetched,
+            prices,
+        };
+        cache.write(path, &candles);
+        fs::File::options()
+            .write(true)
+            .open(cache.dir.join(path))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60))
+            .unwrap();
+    }
+
+    async fn daily(cache: &CachedProvider<FakeProvider>, to: i64) -> Prices {
+        cache
+            .price_history(AssetClass::Stock, "AAPL", "D", 0, to)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fresh_candles_are_served_from_the_cache() {
+        let cache = provider(
+            "hit",
+            FakeProvider {
+                bars: bars(&[0, 86400], &[1.0, 2.0]),
+                ..FakeProvider::default()
+            },
+        );
+        assert_eq!(daily(&cache, 86400).await.close, vec![1.0, 2.0]);
+        assert_eq!(daily(&cache, 86400).await.close, vec![1.0, 2.0]);
+        // A narrower window is cut from the same entry
+        assert_eq!(daily(&cache, 0).await.close, vec![1.0]);
+        assert_eq!(*cache.inner.requests.lock().unwrap(), vec![(0, 86400)]);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_candles_fetch_only_the_newer_bars() {
+        let cache = provider(
+            "append",
+            FakeProvider {
+                bars: bars(&[0, 86400, 172800], &[1.0, 2.5, 3.0]),
+                ..FakeProvider::default()
+            },
+        );
+        cache_expired(&cache, 86400, bars(&[0, 86400], &[1.0, 2.0]));
+
+        let prices = daily(&cache, 200000).await;
+        assert_eq!(prices.timestamps, vec![0, 86400, 172800]);
+        // The last cached bar was still open and is replaced
+        assert_eq!(prices.close, vec![1.0, 2.5, 3.0]);
+        assert_eq!(*cache.inner.requests.lock().unwrap(), vec![(86400, 200000)]);
+
+        // The merged bars are cached again
+        assert_eq!(daily(&cache, 200000).await.close, vec![1.0, 2.5, 3.0]);
+        assert_eq!(cache.inner.requests.lock().unwrap().len(), 1);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_candles_are_served_when_the_fetch_fails() {
+        let cache = provider(
+            "expired",
+            FakeProvider {
+                offline: true,
+                ..FakeProvider::default()
+            },
+        );
+        cache_expired(&cache, 86400, bars(&[0, 86400], &[1.0, 2.0]));
+        assert_eq!(daily(&cache, 200000).await.close, vec![1.0, 2.0]);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_split_after_caching_fetches_every_bar_again() {
+        let may = date_to_timestamp("2020-05-01").unwrap();
+        let july = date_to_timestamp("2020-07-01").unwrap();
+        let split = Split {
+            date: String::from("2020-06-01"),
+            from_factor: 1.0,
+            to_factor: 2.0,
+        };
+        let cached = bars(&[may, july], &[100.0, 60.0]);
+        let fetched = date_to_timestamp("2020-01-01").unwrap();
+
+        let cache = provider(
+            "split",
+            FakeProvider {
+                bars: bars(&[may, july], &[50.0, 61.0]),
+                splits: vec![split.clone()],
+                ..FakeProvider::default()
+            },
+        );
+        cache_expired(&cache, fetched, cached.clone());
+        assert_eq!(daily(&cache, july).await.close, vec![50.0, 61.0]);
+        assert_eq!(*cache.inner.requests.lock().unwrap(), vec![(0, july)]);
+        fs::remove_dir_all(&cache.dir).unwrap();
+
+        // Offline the cached bars are adjusted locally instead
+        let cache = provider(
+            "split-offline",
+            FakeProvider {
+                splits: vec![split],
+                offline: true,
+                ..FakeProvider::default()
+            },
+        );
+        cache_expired(&cache, fetched, cached);
+        assert_eq!(daily(&cache, july).await.close, vec![50.0, 60.0]);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn splits_before_caching_are_already_adjusted_for() {
+        let cache = provider(
+            "old-split",
+            FakeProvider {
+                bars: bars(&[0, 86400, 172800], &[1.0, 2.5, 3.0]),
+                splits: vec![Split {
+                    date: String::from("1970-01-02"),
+                    from_factor: 1.0,
+                    to_factor: 2.0,
+                }],
+                ..FakeProvider::default()
+            },
+        );
+        cache_expired(&cache, 172800, bars(&[0, 86400], &[1.0, 2.0]));
+        daily(&cache, 200000).await;
+        assert_eq!(*cache.inner.requests.lock().unwrap(), vec![(86400, 200000)]);
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use reqwest::StatusCode;
+use std::{error::Error, fmt, io};
+
+/// Everything that can go wrong getting market data
+#[derive(Debug, Clone, PartialEq)]
+pub enum StonksError {
+    /// The server could not be reached or the connection dropped
+    Network(String),
+    /// The server answered with an unexpected status code
+    HttpStatus(u16),
+    /// Too many requests for the API plan
+    RateLimited,
+    /// The API key is missing or was rejected
+    Auth,
+    /// The response did not have the expected format
+    Parse(String),
+    /// There is no data for the requested symbol or time range
+    NoData,
+    /// Reading or writing a local file failed
+    Io(String),
+}
+
+impl StonksError {
+    /// Error for a response with a non-2xx `status`
+    pub fn from_status(status: StatusCode) -> StonksError {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => StonksError::Auth,
+            StatusCode::TOO_MANY_REQUESTS => StonksError::RateLimited,
+            _ => StonksError::HttpStatus(status.as_u16()),
+        }
+    }
+}
+
+impl fmt::Display for StonksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StonksError::Network(msg) => write!(f, "Network error: {}", msg),
+            StonksError::HttpStatus(code) => write!(f, "Unexpected HTTP status {}", code),
+            StonksError::RateLimited => write!(f, "Rate limited by the API, try again shortly"),
+            StonksError::Auth => write!(f, "API key missing or rejected"),
+            StonksError::Parse(msg) => write!(f, "Could not read response: {}", msg),
+            StonksError::NoData => write!(f, "No data available"),
+            StonksError::Io(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for StonksError {}
+
+impl From<reqwest::Error> for StonksError {
+    fn from(err: reqwest::Error) -> StonksError {
+        match err.status() {
+            Some(status) => StonksError::from_status(status),
+            None => StonksError::Network(err.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for StonksError {
+    fn from(err: serde_json::Error) -> StonksError {
+        StonksError::Parse(err.to_string())
+    }
+}
+
+impl From<tungstenite::Error> for StonksError {
+    fn from(err: tungstenite::Error) -> StonksError {
+        match err {
+            tungstenite::Error::Http(status) => StonksError::from_status(status),
+            err => StonksError::Network(err.to_string()),
+        }
+    }
+}
+
+impl From<url::ParseError> for StonksError {
+    fn from(err: url::ParseError) -> StonksError {
+        StonksError::Network(err.to_string())
+    }
+}
+
+impl From<io::Error> for StonksError {
+    fn from(err: io::Error) -> StonksError {
+        StonksError::Io(err.to_string())
+    }
+}
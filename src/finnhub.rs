@@ -0,0 +1,527 @@
+use crate::asset::{
+    AssetClass, CompanyInfo, Dividend, EarningsCalendar, EarningsRelease, EarningsSurprise, Feed,
+    ForexRates, Fundamentals, InsiderTransaction, InsiderTransactions, MarketDataProvider,
+    NewsArticle, PriceTarget, Prices, Quote, RecommendationTrend, Split, Stock,
+};
+use crate::error::StonksError;
+use crate::replay::FeedRecorder;
+use crate::scheduler::{self, Quota, RequestScheduler};
+use crate::subscription::{ConnectionStatus, FeedCommand, FeedEvent};
+use async_trait::async_trait;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use native_tls::TlsStream;
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::{
+    collections::BTreeSet,
+    io::ErrorKind,
+    net::TcpStream,
+    path::PathBuf,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+use tungstenite::{stream::Stream, Message, WebSocket};
+
+const API_URL: &str = "https://finnhub.io/api/v1";
+const WEBSOCKET_URL: &str = "wss://ws.finnhub.io";
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// A connection this quiet gets pinged
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+/// A connection this quiet is assumed dead and replaced
+const STALE_AFTER: Duration = Duration::from_secs(90);
+
+/// Market data from the finnhub.io REST and websocket APIs
+pub struct Finnhub {
+    api_key: String,
+    record_path: Option<PathBuf>,
+    scheduler: RequestScheduler,
+    client: reqwest::Client,
+}
+
+impl Finnhub {
+    pub fn new(api_key: String) -> Finnhub {
+        Finnhub {
+            api_key,
+            record_path: None,
+            scheduler: RequestScheduler::new(scheduler::FREE_TIER_CALLS_PER_MINUTE),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Raises the request quota for paid plans
+    pub fn calls_per_minute(mut self, limit: usize) -> Finnhub {
+        self.scheduler = RequestScheduler::new(limit);
+        self
+    }
+
+    /// Writes every raw websocket frame to `path` for later replay
+    pub fn record_feed<P: Into<PathBuf>>(mut self, path: P) -> Finnhub {
+        self.record_path = Some(path.into());
+        self
+    }
+
+    fn connect(&self) -> Result<Socket, StonksError> {
+        let url = Url::parse(&format!("{}?token={}", WEBSOCKET_URL, self.api_key))?;
+        let (socket, response) = tungstenite::connect(url)?;
+        for (ref header, _value) in response.headers() {
+            info!("ws headers: {}", header);
+        }
+        // Wake up regularly to pass on subscription changes
+        set_read_timeout(&socket, Some(COMMAND_POLL_INTERVAL))?;
+        Ok(socket)
+    }
+}
+
+#[async_trait]
+impl MarketDataProvider for Finnhub {
+    async fn company_profile(&self, symbol: &str) -> Result<CompanyInfo, StonksError> {
+        get_equity(self, symbol).await
+    }
+
+    async fn exchanges(&self, asset_class: AssetClass) -> Result<Vec<String>, StonksError> {
+        get(self, &format!("{}/exchange", asset_class.path())).await
+    }
+
+    async fn securities(
+        &self,
+        asset_class: AssetClass,
+        exchange: &str,
+    ) -> Result<Vec<Stock>, StonksError> {
+        get_all_securites(self, asset_class, exchange).await
+    }
+
+    async fn quote(&self, symbol: &str) -> Result<Quote, StonksError> {
+        let quote: Quote = get(self, &format!("quote?symbol={}", symbol)).await?;
+        if quote.is_empty() {
+            return Err(StonksError::NoData);
+        }
+        Ok(quote)
+    }
+
+    async fn company_news(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<NewsArticle>, StonksError> {
+        let path = format!("company-news?symbol={}&from={}&to={}", symbol, from, to);
+        get(self, &path).await
+    }
+
+    async fn market_news(&self, category: &str) -> Result<Vec<NewsArticle>, StonksError> {
+        get(self, &format!("news?category={}", category)).await
+    }
+
+    async fn earnings_calendar(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<EarningsRelease>, StonksError> {
+        let path = format!(
+            "calendar/earnings?symbol={}&from={}&to={}",
+            symbol, from, to
+        );
+        let calendar: EarningsCalendar = get(self, &path).await?;
+        Ok(calendar.earnings)
+    }
+
+    async fn earnings_surprises(&self, symbol: &str) -> Result<Vec<EarningsSurprise>, StonksError> {
+        get(self, &format!("stock/earnings?symbol={}", symbol)).await
+    }
+
+    async fn fundamentals(&self, symbol: &str) -> Result<Fundamentals, StonksError> {
+        get(self, &format!("stock/metric?symbol={}&metric=all", symbol)).await
+    }
+
+    async fn recommendations(&self, symbol: &str) -> Result<Vec<RecommendationTrend>, StonksError> {
+        get(self, &format!("stock/recommendation?symbol={}", symbol)).await
+    }
+
+    async fn price_target(&self, symbol: &str) -> Result<PriceTarget, StonksError> {
+        let target: PriceTarget =
+            get(self, &format!("stock/price-target?symbol={}", symbol)).await?;
+        if target.is_empty() {
+            return Err(StonksError::NoData);
+        }
+        Ok(target)
+    }
+
+    async fn peers(&self, symbol: &str) -> Result<Vec<String>, StonksError> {
+        get(self, &format!("stock/peers?symbol={}", symbol)).await
+    }
+
+    async fn dividends(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<Dividend>, StonksError> {
+        let path = format!("stock/dividend?symbol={}&from={}&to={}", symbol, from, to);
+        get(self, &path).await
+    }
+
+    async fn splits(&self, symbol: &str, from: &str, to: &str) -> Result<Vec<Split>, StonksError> {
+        let path = format!("stock/split?symbol={}&from={}&to={}", symbol, from, to);
+        get(self, &path).await
+    }
+
+    async fn insider_transactions(
+        &self,
+        symbol: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<InsiderTransaction>, StonksError> {
+        let path = format!(
+            "stock/insider-transactions?symbol={}&from={}&to={}",
+            symbol, from, to
+        );
+        let transactions: InsiderTransactions = get(self, &path).await?;
+        Ok(transactions.data)
+    }
+
+    async fn forex_rates(&self, base: &str) -> Result<ForexRates, StonksError> {
+        get(self, &format!("forex/rates?base={}", base)).await
+    }
+
+    async fn price_history(
+        &self,
+        asset_class: AssetClass,
+        symbol: &str,
+        resolution: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Prices, StonksError> {
+        get_price_history(self, asset_class, symbol, resolution, from, to).await
+    }
+
+    /// Reconnects with backoff whenever the socket drops, and subscribes to
+    /// everything again on the new connection.
+    fn live_feed(
+        &self,
+        commands: Receiver<FeedCommand>,
+        tx: Sender<FeedEvent>,
+    ) -> Result<(), StonksError> {
+        let mut recorder = match &self.record_path {
+            Some(path) => {
+                info!("Recording live feed to {}", path.display());
+                Some(FeedRecorder::create(path)?)
+            }
+            None => None,
+        };
+
+        let mut symbols = BTreeSet::new();
+        let mut attempt = 0;
+        let _ = tx.send(FeedEvent::Status(ConnectionStatus::Connecting));
+        loop {
+            let res = self.connect().and_then(|mut socket| {
+                attempt = 0;
+                let _ = tx.send(FeedEvent::Status(ConnectionStatus::Connected));
+                live_price(&mut socket, &commands, &tx, &mut symbols, &mut recorder)
+            });
+            match res {
+                Ok(FeedEnd::Stopped) => return Ok(()),
+                Ok(FeedEnd::Closed) => warn!("Live feed closed by the server"),
+                // Retrying will not fix a bad API key
+                Err(StonksError::Auth) => return Err(StonksError::Auth),
+                Err(err) => warn!("Live feed dropped: {}", err),
+            }
+
+            attempt += 1;
+            let delay = reconnect_delay(attempt);
+            info!("Reconnecting live feed in {:?}", delay);
+            let status = FeedEvent::Status(ConnectionStatus::Reconnecting { attempt });
+            if tx.send(status).is_err() || !wait(&commands, &mut symbols, delay) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn quota(&self) -> Option<Quota> {
+        Some(self.scheduler.quota())
+    }
+}
+
+/// Sends an authenticated GET to `path` under the REST API and parses the JSON
+/// body. Waits for quota first, and retries with backoff when rate limited.
+async fn get<T: DeserializeOwned>(finnhub: &Finnhub, path: &str) -> Result<T, StonksError> {
+    let url = format!("{}/{}", API_URL, path);
+    let mut attempt = 0;
+    let resp = loop {
+        finnhub.scheduler.acquire().await;
+        let resp = finnhub
+            .client
+            .get(&url)
+            .header("X-Finnhub-Token", &finnhub.api_key)
+            .send()
+            .await?;
+        info!("{} {}", path, resp.status());
+        finnhub.scheduler.update_from_headers(resp.headers());
+
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS && attempt < scheduler::MAX_RETRIES {
+            let delay = finnhub
+                .scheduler
+                .rate_limited(scheduler::retry_after(resp.headers()), attempt);
+            warn!("Rate limited on {}, retrying in {:?}", path, delay);
+            attempt += 1;
+            continue;
+        }
+        break resp;
+    };
+
+    if !resp.status().is_success() {
+        return Err(StonksError::from_status(resp.status()));
+    }
+    let body = resp.text().await?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+async fn get_equity(finnhub: &Finnhub, symbol: &str) -> Result<CompanyInfo, StonksError> {
+    // Unknown symbols get an empty object rather than an error status
+    let profile: serde_json::Value =
+        get(finnhub, &format!("stock/profile2?symbol={}", symbol)).await?;
+    if profile.as_object().is_none_or(|fields| fields.is_empty()) {
+        return Err(StonksError::NoData);
+    }
+
+    let mut company: CompanyInfo = serde_json::from_value(profile)?;
+    company.market_capitalization *= 1000000.0;
+    info!("{:#?}", company);
+    Ok(company)
+}
+
+async fn get_all_securites(
+    finnhub: &Finnhub,
+    asset_class: AssetClass,
+    exchange: &str,
+) -> Result<Vec<Stock>, StonksError> {
+    let path = format!("{}/symbol?exchange={}", asset_class.path(), exchange);
+    let securities: Vec<Stock> = get(finnhub, &path).await?;
+    info!("Securities on {}: {}", exchange, securities.len());
+    Ok(securities)
+}
+
+async fn get_price_history(
+    finnhub: &Finnhub,
+    asset_class: AssetClass,
+    symbol: &str,
+    resolution: &str,
+    from: i64,
+    to: i64,
+) -> Result<Prices, StonksError> {
+    info!("Candles {} from {} to {}", resolution, from, to);
+    let path = format!(
+        "{}/candle?symbol={}&resolution={}&from={}&to={}",
+        asset_class.path(),
+        symbol,
+        resolution,
+        from,
+        to
+    );
+    let prices: Prices = get(finnhub, &path).await?;
+    if prices.is_no_data() {
+        info!("No price data for {}", symbol);
+    }
+    info!("Prices: {:#?}", prices.len());
+    Ok(prices)
+}
+
+type Socket = WebSocket<Stream<TcpStream, TlsStream<TcpStream>>>;
+
+fn set_read_timeout(socket: &Socket, timeout: Option<Duration>) -> Result<(), StonksError> {
+    match socket.get_ref() {
+        Stream::Plain(stream) => stream.set_read_timeout(timeout)?,
+        Stream::Tls(stream) => stream.get_ref().set_read_timeout(timeout)?,
+    }
+    Ok(())
+}
+
+fn subscription_message(command: &FeedCommand) -> Message {
+    let (message_type, symbol) = match command {
+        FeedCommand::Subscribe(symbol) => ("subscribe", symbol),
+        FeedCommand::Unsubscribe(symbol) => ("unsubscribe", symbol),
+    };
+    Message::Text(json!({ "type": message_type, "symbol": symbol }).to_string())
+}
+
+/// Why streaming from a socket stopped without an error
+enum FeedEnd {
+    /// The server closed the connection
+    Closed,
+    /// The UI has gone away
+    Stopped,
+}
+
+/// What to do about a connection that has been quiet for a while
+#[derive(Debug, PartialEq)]
+enum Silence {
+    Wait,
+    Ping,
+    Stale,
+}
+
+/// Decides on a connection quiet for `silence`, last pinged `since_ping` ago
+fn check_silence(silence: Duration, since_ping: Duration) -> Silence {
+    if silence >= STALE_AFTER {
+        Silence::Stale
+    } else if silence >= PING_INTERVAL && since_ping >= PING_INTERVAL {
+        Silence::Ping
+    } else {
+        Silence::Wait
+    }
+}
+
+/// Pause before reconnect number `attempt`, counting from one
+fn reconnect_delay(attempt: u32) -> Duration {
+    scheduler::backoff(attempt.saturating_sub(1))
+}
+
+/// Keeps the set of symbols to subscribe to on the next connection up to date
+fn track(symbols: &mut BTreeSet<String>, command: &FeedCommand) {
+    match command {
+        FeedCommand::Subscribe(symbol) => symbols.insert(symbol.clone()),
+        FeedCommand::Unsubscribe(symbol) => symbols.remove(symbol),
+    };
+}
+
+/// Sleeps for `delay` while still taking subscription changes. False if the
+/// UI has gone away in the meantime.
+fn wait(commands: &Receiver<FeedCommand>, symbols: &mut BTreeSet<String>, delay: Duration) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        match commands.recv_timeout(deadline - now) {
+            Ok(command) => track(symbols, &command),
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return false,
+        }
+    }
+}
+
+/// Subscribes to `symbols` and streams trades until the connection ends.
+/// Quiet connections are pinged, and given up on if even that gets no answer.
+fn live_price(
+    socket: &mut Socket,
+    commands: &Receiver<FeedCommand>,
+    tx: &Sender<FeedEvent>,
+    symbols: &mut BTreeSet<String>,
+    recorder: &mut Option<FeedRecorder>,
+) -> Result<FeedEnd, StonksError> {
+    for symbol in symbols.iter() {
+        socket.write_message(subscription_message(&FeedCommand::Subscribe(
+            symbol.clone(),
+        )))?;
+    }
+
+    let mut last_frame = Instant::now();
+    let mut last_ping = Instant::now();
+    loop {
+        loop {
+            match commands.try_recv() {
+                Ok(command) => {
+                    info!("Live feed {:?}", command);
+                    track(symbols, &command);
+                    socket.write_message(subscription_message(&command))?;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(FeedEnd::Stopped),
+            }
+        }
+
+        let msg = match socket.read_message() {
+            Ok(msg) => msg,
+            Err(tungstenite::Error::Io(err))
+                if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut =>
+            {
+                let silence = last_frame.elapsed();
+                match check_silence(silence, last_ping.elapsed()) {
+                    Silence::Stale => {
+                        return Err(StonksError::Network(format!(
+                            "nothing received for {:?}",
+                            silence
+                        )))
+                    }
+                    Silence::Ping => {
+                        socket.write_message(Message::Ping(Vec::new()))?;
+                        last_ping = Instant::now();
+                    }
+                    Silence::Wait => {}
+                }
+                continue;
+            }
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(FeedEnd::Closed),
+            Err(err) => return Err(err.into()),
+        };
+        last_frame = Instant::now();
+
+        match msg {
+            Message::Text(text) => {
+                if let Some(recorder) = recorder {
+                    if let Err(err) = recorder.record(&text) {
+                        error!("Could not record live feed: {}", err);
+                    }
+                }
+                match Feed::parse(&text) {
+                    Ok(feed) if feed.message_type == "ping" => trace!("Live feed ping"),
+                    Ok(feed) => {
+                        let trades = feed.trades();
+                        if !trades.is_empty() && tx.send(FeedEvent::Trades(trades)).is_err() {
+                            return Ok(FeedEnd::Stopped);
+                        }
+                    }
+                    Err(err) => warn!("Skipping websocket message {}: {}", text, err),
+                }
+            }
+            Message::Close(frame) => {
+                info!("Live feed close frame: {:?}", frame);
+                return Ok(FeedEnd::Closed);
+            }
+            // tungstenite answers pings by itself
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnects_back_off_up_to_a_minute() {
+        let secs = Duration::from_secs;
+        assert_eq!(reconnect_delay(1), secs(1));
+        assert_eq!(reconnect_delay(2), secs(2));
+        assert_eq!(reconnect_delay(4), secs(8));
+        assert_eq!(reconnect_delay(7), secs(60));
+        assert_eq!(reconnect_delay(100), secs(60));
+    }
+
+    #[test]
+    fn quiet_connections_are_pinged_then_dropped() {
+        let secs = Duration::from_secs;
+        assert_eq!(check_silence(secs(5), secs(5)), Silence::Wait);
+        assert_eq!(check_silence(secs(30), secs(30)), Silence::Ping);
+        // One ping per interval, not one per poll
+        assert_eq!(check_silence(secs(45), secs(15)), Silence::Wait);
+        assert_eq!(check_silence(secs(60), secs(30)), Silence::Ping);
+        assert_eq!(check_silence(secs(90), secs(0)), Silence::Stale);
+    }
+
+    #[test]
+    fn tracks_subscriptions_for_the_next_connection() {
+        let mut symbols = BTreeSet::new();
+        track(&mut symbols, &FeedCommand::Subscribe(String::from("AAPL")));
+        track(&mut symbols, &FeedCommand::Subscribe(String::from("MSFT")));
+        track(
+            &mut symbols,
+            &FeedCommand::Unsubscribe(String::from("AAPL")),
+        );
+        assert_eq!(symbols.into_iter().collect::<Vec<_>>(), vec!["MSFT"]);
+    }
+}
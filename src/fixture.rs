@@ -0,0 +1,202 @@
+use crate::asset::{
+    AssetClass, CompanyInfo, Dividend, EarningsCalendar, EarningsRelease, EarningsSurprise,
+    ForexRates, Fundamentals, InsiderTransaction, InsiderTransactions, MarketDataProvider,
+    NewsArticle, PriceTarget, Prices, Quote, RecommendationTrend, Split, Stock,
+};
+use crate::cache::file_name;
+use crate::error::StonksError;
+use crate::subscription::{FeedCommand, FeedEvent};
+use async_trait::async_trait;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use mpsc::{Receiver, Sender};
+use serde::de::DeserializeOwned;
+use std::{fs, io::ErrorKind, path::PathBuf, sync::mpsc};
+
+/// Serves recorded finnhub.io responses from a directory so the app can run
+/// without an API key or a network connection.
+///
+/// Layout of the directory:
+/// - `symbols/<EXCHANGE>.json`: response of `stock/symbol`
+/// - `profile/<SYMBOL>.json`: response of `stock/profile2`
+/// - `quote/<SYMBOL>.json`: response of `quote`
+/// - `news/<SYMBOL>.json` and `news/category/<category>.json`: responses of
+///   `company-news` and `news`
+/// - `earnings/calendar/<SYMBOL>.json` and `earnings/<SYMBOL>.json`:
+///   responses of `calendar/earnings` and `stock/earnings`
+/// - `metric/<SYMBOL>.json`: response of `stock/metric`
+/// - `recommendation/<SYMBOL>.json` and `price-target/<SYMBOL>.json`:
+///   responses of `stock/recommendation` and `stock/price-target`
+/// - `peers/<SYMBOL>.json`: response of `stock/peers`
+/// - `dividend/<SYMBOL>.json` and `split/<SYMBOL>.json`: responses of
+///   `stock/dividend` and `stock/split`
+/// - `insider-transactions/<SYMBOL>.json`: response of
+///   `stock/insider-transactions`
+/// - `candle/<SYMBOL>.json`: response of `stock/candle`, optionally per
+///   resolution as `candle/<SYMBOL>_<resolution>.json`
+/// - `crypto/exchanges.json`, `crypto/symbols/` and `crypto/candle/`: the
+///   same for crypto pairs, with `:` in symbols written as `_`
+/// - `forex/...`: the same for forex pairs, plus `forex/rates/<BASE>.json`
+///   from `forex/rates`
+pub struct FixtureProvider {
+    dir: PathBuf,
+}
+
+impl FixtureProvider {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> FixtureProvider {
+        FixtureProvider { dir: dir.into() }
+    }
+
+    /// A missing fixture is reported as `NoData`, like an unknown symbol would
+    /// be by the real API
+    fn load<T: DeserializeOwned>(&self, path: &str) -> Result<T, StonksError> {
+        let path = self.dir.join(path);
+        info!("Loading fixture {}", path.display());
+        let text = fs::read_to_string(&path).map_err(|err| match err.kind() {
+            ErrorKind::NotFound => StonksError::NoData,
+            _ => StonksError::Io(format!("Cannot read fixture {}: {}", path.display(), err)),
+        })?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[async_trait]
+impl MarketDataProvider for FixtureProvider {
+    async fn company_profile(&self, symbol: &str) -> Result<CompanyInfo, StonksError> {
+        let mut company: CompanyInfo = self.load(&format!("profile/{}.json", symbol))?;
+        company.market_capitalization *= 1000000.0;
+        Ok(company)
+    }
+
+    async fn exchanges(&self, asset_class: AssetClass) -> Result<Vec<String>, StonksError> {
+        self.load(&format!("{}exchanges.json", prefix(asset_class)))
+    }
+
+    async fn securities(
+        &self,
+        asset_class: AssetClass,
+        exchange: &str,
+    ) -> Result<Vec<Stock>, StonksError> {
+        self.load(&format!("{}symbols/{}.json", prefix(asset_class), exchange))
+    }
+
+    /// Serves `quote/<SYMBOL>.json`
+    async fn quote(&self, symbol: &str) -> Result<Quote, StonksError> {
+        self.load(&format!("quote/{}.json", symbol))
+    }
+
+    async fn company_news(
+        &self,
+        symbol: &str,
+        _from: &str,
+        _to: &str,
+    ) -> Result<Vec<NewsArticle>, StonksError> {
+        self.load(&format!("news/{}.json", symbol))
+    }
+
+    async fn market_news(&self, category: &str) -> Result<Vec<NewsArticle>, StonksError> {
+        self.load(&format!("news/category/{}.json", category))
+    }
+
+    async fn earnings_calendar(
+        &self,
+        symbol: &str,
+        _from: &str,
+        _to: &str,
+    ) -> Result<Vec<EarningsRelease>, StonksError> {
+        let calendar: EarningsCalendar =
+            self.load(&format!("earnings/calendar/{}.json", symbol))?;
+        Ok(calendar.earnings)
+    }
+
+    async fn earnings_surprises(&self, symbol: &str) -> Result<Vec<EarningsSurprise>, StonksError> {
+        self.load(&format!("earnings/{}.json", symbol))
+    }
+
+    async fn fundamentals(&self, symbol: &str) -> Result<Fundamentals, StonksError> {
+        self.load(&format!("metric/{}.json", symbol))
+    }
+
+    async fn recommendations(&self, symbol: &str) -> Result<Vec<RecommendationTrend>, StonksError> {
+        self.load(&format!("recommendation/{}.json", symbol))
+    }
+
+    async fn price_target(&self, symbol: &str) -> Result<PriceTarget, StonksError> {
+        self.load(&format!("price-target/{}.json", symbol))
+    }
+
+    async fn peers(&self, symbol: &str) -> Result<Vec<String>, StonksError> {
+        self.load(&format!("peers/{}.json", symbol))
+    }
+
+    async fn dividends(
+        &self,
+        symbol: &str,
+        _from: &str,
+        _to: &str,
+    ) -> Result<Vec<Dividend>, StonksError> {
+        self.load(&format!("dividend/{}.json", symbol))
+    }
+
+    async fn splits(
+        &self,
+        symbol: &str,
+        _from: &str,
+        _to: &str,
+    ) -> Result<Vec<Split>, StonksError> {
+        self.load(&format!("split/{}.json", symbol))
+    }
+
+    async fn insider_transactions(
+        &self,
+        symbol: &str,
+        _from: &str,
+        _to: &str,
+    ) -> Result<Vec<InsiderTransaction>, StonksError> {
+        let transactions: InsiderTransactions =
+            self.load(&format!("insider-transactions/{}.json", symbol))?;
+        Ok(transactions.data)
+    }
+
+    async fn forex_rates(&self, base: &str) -> Result<ForexRates, StonksError> {
+        self.load(&format!("forex/rates/{}.json", base))
+    }
+
+    /// Serves `candle/<SYMBOL>_<resolution>.json` if it was recorded, otherwise
+    /// `candle/<SYMBOL>.json`. The whole recording is returned regardless of
+    /// `from` and `to`.
+    async fn price_history(
+        &self,
+        asset_class: AssetClass,
+        symbol: &str,
+        resolution: &str,
+        _from: i64,
+        _to: i64,
+    ) -> Result<Prices, StonksError> {
+        let dir = format!("{}candle", prefix(asset_class));
+        let symbol = file_name(symbol);
+        let path = format!("{}/{}_{}.json", dir, symbol, resolution);
+        if self.dir.join(&path).exists() {
+            self.load(&path)
+        } else {
+            self.load(&format!("{}/{}.json", dir, symbol))
+        }
+    }
+
+    fn live_feed(
+        &self,
+        _commands: Receiver<FeedCommand>,
+        _tx: Sender<FeedEvent>,
+    ) -> Result<(), StonksError> {
+        info!("No live prices from fixtures");
+        Ok(())
+    }
+}
+
+/// Stock fixtures sit at the top of the directory, other classes below it
+fn prefix(asset_class: AssetClass) -> String {
+    match asset_class {
+        AssetClass::Stock => String::new(),
+        _ => format!("{}/", asset_class.path()),
+    }
+}
@@ -1,20 +1,20 @@
 use app::App;
-use config;
+use asset::MarketDataProvider;
 use crossterm::{
-    event::{self, DisableMouseCapture, Event as CEvent, KeyCode},
+    event::{self, DisableMouseCapture, Event as CEvent, KeyCode, KeyEvent},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, LeaveAlternateScreen},
 };
 use io::Stdout;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
-use mpsc::Receiver;
-use reqwest::Url;
+use mpsc::{Receiver, Sender};
 use std::io;
 use std::{
     collections::HashMap,
     io::Write,
-    sync::mpsc,
+    path::PathBuf,
+    sync::{mpsc, Arc},
     thread,
     time::{Duration, Instant},
 };
@@ -22,7 +22,16 @@ use tui::backend::CrosstermBackend;
 use tui::Terminal;
 mod app;
 mod asset;
+mod cache;
+mod error;
+mod finnhub;
+mod fixture;
+mod market;
+mod replay;
+mod scheduler;
 mod search_page;
+mod service;
+mod subscription;
 mod ui;
 mod util;
 
@@ -31,6 +40,13 @@ extern crate chrono;
 enum Event<I> {
     Input(I),
     Tick,
+    Data(Box<service::DataEvent>),
+}
+
+impl<I> From<service::DataEvent> for Event<I> {
+    fn from(event: service::DataEvent) -> Event<I> {
+        Event::Data(Box::new(event))
+    }
 }
 
 fn main() -> Result<(), io::Error> {
@@ -46,56 +62,58 @@ fn main() -> Result<(), io::Error> {
     terminal.show_cursor().expect("Could not show cursor");
     enable_raw_mode().unwrap();
 
-    let mut app = App::new("Stonks", String::from("TSLA"), config);
+    let provider = get_provider(&config);
+    let (tx, rx) = mpsc::channel();
+    let data_service = service::DataService::spawn(provider.clone(), tx.clone());
 
-    // Live prices websocket
+    // Live prices websocket, subscriptions follow the selected symbol
     let (wstx, wsrx) = mpsc::channel();
-    let api_key = app
-        .config
-        .get("api_key")
-        .expect("Could not get api_key")
-        .to_string();
-    let symbol = app.symbol.to_string();
-    app.securities = asset::get_all_securites(&api_key).unwrap();
-    app.search_engine = Some(search_page::SearchEngine::new(&mut app.securities));
-
-    // Spawn websocket thread
+    let (feed_tx, feed_rx) = mpsc::channel();
+    let subscriptions = subscription::SubscriptionManager::new(feed_tx);
+
+    let mut app = App::new(String::from("TSLA"), config, data_service, subscriptions);
+    app.load_securities();
+    app.load_company();
+
+    // Spawn websocket thread, or play back a recorded feed instead
+    let replay_path = app.config.get("replay_feed").cloned();
+    let replay_speed = match app.config.get("replay_speed") {
+        Some(speed) => replay::ReplaySpeed::parse(speed).unwrap_or_else(|| {
+            warn!("Invalid replay_speed {:?}, replaying at 1x", speed);
+            replay::ReplaySpeed::Times(1.0)
+        }),
+        None => replay::ReplaySpeed::Times(1.0),
+    };
+    let status_tx = wstx.clone();
     thread::spawn(move || {
-        let (mut socket, response) = tungstenite::connect(
-            Url::parse(&format!("wss://ws.finnhub.io?token={}", api_key)).unwrap(),
-        )
-        .expect("cannot connect to websocket");
-
-        // A WebSocket echo server
-        let message_text = format!(
-            "{{\"type\":\"subscribe\",\"symbol\":\"{}\"}}",
-            //"BINANCE:BTCUSDT"
-            symbol
-        );
-        let subscribe_msg = tungstenite::Message::Text(String::from(message_text));
-        socket.write_message(subscribe_msg).unwrap();
-        for (ref header, _value) in response.headers() {
-            info!("ws headers: {}", header);
+        let res = match replay_path {
+            Some(path) => replay::replay(path, replay_speed, wstx),
+            None => provider.live_feed(feed_rx, wstx),
+        };
+        if let Err(err) = res {
+            error!("Live price feed stopped: {}", err);
         }
-
-        asset::live_price(socket, wstx);
+        let _ = status_tx.send(subscription::FeedEvent::Status(
+            subscription::ConnectionStatus::Offline,
+        ));
     });
     terminal.clear()?;
     terminal.hide_cursor().unwrap();
 
-    setup_input_handler(&mut app, &mut terminal, wsrx).unwrap();
+    setup_input_handler(&mut app, &mut terminal, tx, rx, wsrx).unwrap();
 
     terminal.clear()?;
     Ok(())
 }
 
 fn setup_input_handler(
-    mut app: &mut App,
+    app: &mut App,
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
-    wsrx: Receiver<(String, f64)>,
+    tx: Sender<Event<KeyEvent>>,
+    rx: Receiver<Event<KeyEvent>>,
+    wsrx: Receiver<subscription::FeedEvent>,
 ) -> Result<(), io::Error> {
     // Setup input handling
-    let (tx, rx) = mpsc::channel();
 
     let tick_rate = Duration::from_millis(200);
     thread::spawn(move || {
@@ -115,7 +133,10 @@ fn setup_input_handler(
     });
 
     loop {
-        terminal.draw(|mut f| ui::draw(&mut f, &mut app, &wsrx))?;
+        for event in wsrx.try_iter() {
+            app.on_feed(event);
+        }
+        terminal.draw(|mut f| ui::draw(&mut f, app))?;
         match rx.recv().unwrap() {
             Event::Input(event) => match event.code {
                 KeyCode::Char('q') => {
@@ -142,6 +163,7 @@ fn setup_input_handler(
             Event::Tick => {
                 app.on_tick();
             }
+            Event::Data(event) => app.on_data(*event),
         }
         if app.should_quit {
             break;
@@ -170,6 +192,54 @@ fn setup_logger() -> Result<(), fern::InitError> {
 fn get_config() -> HashMap<String, String> {
     let mut settings = config::Config::default();
 
-    settings.merge(config::File::with_name("config")).unwrap();
+    settings
+        .merge(config::File::with_name("config").required(false))
+        .unwrap();
+    settings
+        .merge(config::Environment::with_prefix("STONKS"))
+        .unwrap();
     settings.try_into::<HashMap<String, String>>().unwrap()
 }
+
+/// Picks the market data backend named by `provider` in the config, defaulting
+/// to finnhub.io.
+fn get_provider(config: &HashMap<String, String>) -> Arc<dyn MarketDataProvider> {
+    match config.get("provider").map(String::as_str) {
+        Some("fixture") => {
+            let dir = config
+                .get("fixture_dir")
+                .map(String::as_str)
+                .unwrap_or("fixtures");
+            info!("Using fixture data from {}", dir);
+            Arc::new(fixture::FixtureProvider::new(dir))
+        }
+        _ => {
+            let api_key = config.get("api_key").cloned().unwrap_or_else(|| {
+                warn!("No api_key in config");
+                String::new()
+            });
+            let mut finnhub = finnhub::Finnhub::new(api_key);
+            if let Some(path) = config.get("record_feed") {
+                finnhub = finnhub.record_feed(path);
+            }
+            if let Some(limit) = config
+                .get("calls_per_minute")
+                .and_then(|limit| limit.parse().ok())
+            {
+                finnhub = finnhub.calls_per_minute(limit);
+            }
+
+            let cache_dir = config
+                .get("cache_dir")
+                .map(PathBuf::from)
+                .or_else(cache::default_cache_dir);
+            match cache_dir {
+                Some(dir) => {
+                    info!("Caching responses in {}", dir.display());
+                    Arc::new(cache::CachedProvider::new(finnhub, dir))
+                }
+                None => Arc::new(finnhub),
+            }
+        }
+    }
+}
@@ -0,0 +1,716 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+/// Part of the trading day a market is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Session {
+    PreMarket,
+    Open,
+    /// Midday break of the Asian exchanges
+    Lunch,
+    AfterHours,
+    Closed,
+}
+
+/// Session of a market right now and the holiday it is closed for, if any
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarketStatus {
+    pub session: Session,
+    pub holiday: Option<&'static str>,
+    /// False on weekdays of an exchange whose holidays are not known, when it
+    /// may be closed after all
+    pub holidays_known: bool,
+}
+
+impl MarketStatus {
+    pub fn label(&self) -> String {
+        let label = match (self.session, self.holiday) {
+            (Session::Closed, Some(holiday)) => format!("Closed ({})", holiday),
+            (Session::Closed, None) => String::from("Closed"),
+            (Session::PreMarket, _) => String::from("Pre-market"),
+            (Session::Open, _) => String::from("Open"),
+            (Session::Lunch, _) => String::from("Lunch break"),
+            (Session::AfterHours, _) => String::from("After-hours"),
+        };
+        if self.holidays_known {
+            label
+        } else {
+            format!("{} (holidays unknown)", label)
+        }
+    }
+}
+
+/// Trading hours of a stock exchange in its local time, as minutes after
+/// midnight. Exchanges without extended hours have them equal to the regular
+/// session.
+pub struct Exchange {
+    /// Code finnhub uses for the exchange's symbol list, also the suffix of
+    /// its symbols outside the US
+    pub code: &'static str,
+    pub name: &'static str,
+    pub timezone: Tz,
+    pre_market: u32,
+    open: u32,
+    close: u32,
+    after_hours: u32,
+    /// Start and end of a midday break, if it has one
+    lunch: Option<(u32, u32)>,
+    /// `None` where they are not known
+    holidays: Option<fn(i32) -> Vec<Holiday>>,
+}
+
+/// A day an exchange closes, or closes early at `early_close`
+struct Holiday {
+    date: NaiveDate,
+    name: &'static str,
+    early_close: Option<u32>,
+}
+
+/// Holidays of the Asian exchanges follow the lunar calendar and yearly
+/// announcements, so they are not known
+static EXCHANGES: [Exchange; 6] = [
+    Exchange {
+        code: "US",
+        name: "NYSE",
+        timezone: Tz::America__New_York,
+        pre_market: 4 * 60,
+        open: 9 * 60 + 30,
+        close: 16 * 60,
+        after_hours: 20 * 60,
+        lunch: None,
+        holidays: Some(us_holidays),
+    },
+    Exchange {
+        code: "L",
+        name: "LSE",
+        timezone: Tz::Europe__London,
+        pre_market: 8 * 60,
+        open: 8 * 60,
+        close: 16 * 60 + 30,
+        after_hours: 16 * 60 + 30,
+        lunch: None,
+        holidays: Some(lse_holidays),
+    },
+    Exchange {
+        code: "DE",
+        name: "XETRA",
+        timezone: Tz::Europe__Berlin,
+        pre_market: 9 * 60,
+        open: 9 * 60,
+        close: 17 * 60 + 30,
+        after_hours: 17 * 60 + 30,
+        lunch: None,
+        holidays: Some(xetra_holidays),
+    },
+    Exchange {
+        code: "TO",
+        name: "TSX",
+        timezone: Tz::America__Toronto,
+        pre_market: 9 * 60 + 30,
+        open: 9 * 60 + 30,
+        close: 16 * 60,
+        after_hours: 16 * 60,
+        lunch: None,
+        holidays: Some(tsx_holidays),
+    },
+    Exchange {
+        code: "HK",
+        name: "HKEX",
+        timezone: Tz::Asia__Hong_Kong,
+        pre_market: 9 * 60 + 30,
+        open: 9 * 60 + 30,
+        close: 16 * 60,
+        after_hours: 16 * 60,
+        lunch: Some((12 * 60, 13 * 60)),
+        holidays: None,
+    },
+    Exchange {
+        code: "T",
+        name: "TSE",
+        timezone: Tz::Asia__Tokyo,
+        pre_market: 9 * 60,
+        open: 9 * 60,
+        close: 15 * 60,
+        after_hours: 15 * 60,
+        lunch: Some((11 * 60 + 30, 12 * 60 + 30)),
+        holidays: None,
+    },
+];
+
+/// Where an asset trades and so when it can be traded
+#[derive(Clone, Copy)]
+pub enum Market {
+    Exchange(&'static Exchange),
+    /// Trades around the clock
+    Crypto,
+    /// Trades from Sunday to Friday 17:00 in New York
+    Forex,
+}
+
+impl Market {
+    /// Market of a stock listed on the exchange `code` from a symbol list,
+    /// otherwise guessed from the suffix of `symbol` like `BARC.L`. Symbols
+    /// matching neither are taken to be American.
+    pub fn for_stock(symbol: &str, code: Option<&str>) -> Market {
+        let suffix = symbol.rsplit_once('.').map(|(_, suffix)| suffix);
+        let exchange = code
+            .and_then(exchange)
+            .or_else(|| suffix.and_then(exchange))
+            .unwrap_or(&EXCHANGES[0]);
+        Market::Exchange(exchange)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Market::Exchange(exchange) => exchange.name,
+            Market::Crypto => "Crypto",
+            Market::Forex => "Forex",
+        }
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> MarketStatus {
+        match self {
+            Market::Exchange(exchange) => exchange.status(now),
+            Market::Crypto => MarketStatus {
+                session: Session::Open,
+                holiday: None,
+                holidays_known: true,
+            },
+            Market::Forex => {
+                let local = now.with_timezone(&Tz::America__New_York);
+                let open = match local.weekday() {
+                    Weekday::Sat => false,
+                    Weekday::Sun => local.hour() >= 17,
+                    Weekday::Fri => local.hour() < 17,
+                    _ => true,
+                };
+                MarketStatus {
+                    session: if open { Session::Open } else { Session::Closed },
+                    holiday: None,
+                    holidays_known: true,
+                }
+            }
+        }
+    }
+}
+
+impl Exchange {
+    pub fn status(&self, now: DateTime<Utc>) -> MarketStatus {
+        let local = now.with_timezone(&self.timezone);
+        let date = local.naive_local().date();
+        let closed = |holiday| MarketStatus {
+            session: Session::Closed,
+            holiday,
+            holidays_known: true,
+        };
+        if date.weekday() == Weekday::Sat || date.weekday() == Weekday::Sun {
+            return closed(None);
+        }
+        let holiday = self.holidays.and_then(|holidays| {
+            holidays(date.year())
+                .into_iter()
+                .find(|holiday| holiday.date == date)
+        });
+        let close = match &holiday {
+            Some(holiday) => match holiday.early_close {
+                Some(close) => close,
+                None => return closed(Some(holiday.name)),
+            },
+            None => self.close,
+        };
+        // Extended hours are cut short by an early close as well
+        let after_hours = close + (self.after_hours - self.close);
+
+        let minutes = local.hour() * 60 + local.minute();
+        let session = if minutes < self.pre_market {
+            Session::Closed
+        } else if minutes < self.open {
+            Session::PreMarket
+        } else if self
+            .lunch
+            .is_some_and(|(start, end)| start <= minutes && minutes < end)
+        {
+            Session::Lunch
+        } else if minutes < close {
+            Session::Open
+        } else if minutes < after_hours {
+            Session::AfterHours
+        } else {
+            Session::Closed
+        };
+        MarketStatus {
+            session,
+            holiday: None,
+            holidays_known: self.holidays.is_some(),
+        }
+    }
+}
+
+/// Exchange with the finnhub.io code `code`
+fn exchange(code: &str) -> Option<&'static Exchange> {
+    EXCHANGES.iter().find(|exchange| exchange.code == code)
+}
+
+/// NYSE and Nasdaq holidays and early closes in `year`
+fn us_holidays(year: i32) -> Vec<Holiday> {
+    let holiday = |date, name| Holiday {
+        date,
+        name,
+        early_close: None,
+    };
+    let early_close = |date, name| Holiday {
+        date,
+        name,
+        early_close: Some(13 * 60),
+    };
+    let new_year = ymd(year, 1, 1);
+    let independence = ymd(year, 7, 4);
+    let thanksgiving = nth_weekday(year, 11, Weekday::Thu, 4);
+    let christmas = ymd(year, 12, 25);
+
+    let mut holidays = vec![
+        holiday(
+            nth_weekday(year, 1, Weekday::Mon, 3),
+            "Martin Luther King Day",
+        ),
+        holiday(nth_weekday(year, 2, Weekday::Mon, 3), "Presidents' Day"),
+        holiday(easter(year) - Duration::days(2), "Good Friday"),
+        holiday(last_weekday(year, 5, Weekday::Mon), "Memorial Day"),
+        holiday(observed(independence), "Independence Day"),
+        holiday(nth_weekday(year, 9, Weekday::Mon, 1), "Labor Day"),
+        holiday(thanksgiving, "Thanksgiving"),
+        early_close(thanksgiving + Duration::days(1), "Day after Thanksgiving"),
+        holiday(observed(christmas), "Christmas"),
+    ];
+    // Not made up on the Friday before when it falls on a Saturday
+    if new_year.weekday() != Weekday::Sat {
+        holidays.push(holiday(observed(new_year), "New Year's Day"));
+    }
+    if year >= 2022 {
+        let juneteenth = ymd(year, 6, 19);
+        holidays.push(holiday(observed(juneteenth), "Juneteenth"));
+    }
+    // The eves only close early when the holiday itself is midweek
+    if is_midweek(independence) {
+        holidays.push(early_close(
+            independence - Duration::days(1),
+            "Independence Day eve",
+        ));
+    }
+    if is_midweek(christmas) {
+        holidays.push(early_close(christmas - Duration::days(1), "Christmas Eve"));
+    }
+    holidays
+}
+
+/// London Stock Exchange holidays and early closes in `year`. One-off bank
+/// holidays, like those for coronations and jubilees, are left out.
+fn lse_holidays(year: i32) -> Vec<Holiday> {
+    let holiday = |date, name| Holiday {
+        date,
+        name,
+        early_close: None,
+    };
+    let early_close = |date, name| Holiday {
+        date,
+        name,
+        early_close: Some(12 * 60 + 30),
+    };
+    let (christmas, boxing_day) = christmas_and_boxing_day(year);
+    let mut holidays = vec![
+        holiday(next_monday(ymd(year, 1, 1)), "New Year's Day"),
+        holiday(easter(year) - Duration::days(2), "Good Friday"),
+        holiday(easter(year) + Duration::days(1), "Easter Monday"),
+        holiday(
+            nth_weekday(year, 5, Weekday::Mon, 1),
+            "Early May bank holiday",
+        ),
+        holiday(last_weekday(year, 5, Weekday::Mon), "Spring bank holiday"),
+        holiday(last_weekday(year, 8, Weekday::Mon), "Summer bank holiday"),
+        holiday(christmas, "Christmas"),
+        holiday(boxing_day, "Boxing Day"),
+    ];
+    for (date, name) in &[
+        (ymd(year, 12, 24), "Christmas Eve"),
+        (ymd(year, 12, 31), "New Year's Eve"),
+    ] {
+        if date.weekday() != Weekday::Sat && date.weekday() != Weekday::Sun {
+            holidays.push(early_close(*date, name));
+        }
+    }
+    holidays
+}
+
+/// Xetra trading holidays in `year`. Those falling on a weekend are not made
+/// up.
+fn xetra_holidays(year: i32) -> Vec<Holiday> {
+    let holiday = |date, name| Holiday {
+        date,
+        name,
+        early_close: None,
+    };
+    vec![
+        holiday(ymd(year, 1, 1), "New Year's Day"),
+        holiday(easter(year) - Duration::days(2), "Good Friday"),
+        holiday(easter(year) + Duration::days(1), "Easter Monday"),
+        holiday(ymd(year, 5, 1), "Labour Day"),
+        holiday(ymd(year, 12, 24), "Christmas Eve"),
+        holiday(ymd(year, 12, 25), "Christmas"),
+        holiday(ymd(year, 12, 26), "Boxing Day"),
+        holiday(ymd(year, 12, 31), "New Year's Eve"),
+    ]
+}
+
+/// Toronto Stock Exchange holidays in `year`
+fn tsx_holidays(year: i32) -> Vec<Holiday> {
+    let holiday = |date, name| Holiday {
+        date,
+        name,
+        early_close: None,
+    };
+    let may_24 = ymd(year, 5, 24);
+    let (christmas, boxing_day) = christmas_and_boxing_day(year);
+    let mut holidays = vec![
+        holiday(next_monday(ymd(year, 1, 1)), "New Year's Day"),
+        holiday(easter(year) - Duration::days(2), "Good Friday"),
+        holiday(
+            may_24 - Duration::days(may_24.weekday().num_days_from_monday().into()),
+            "Victoria Day",
+        ),
+        holiday(next_monday(ymd(year, 7, 1)), "Canada Day"),
+        holiday(nth_weekday(year, 8, Weekday::Mon, 1), "Civic Holiday"),
+        holiday(nth_weekday(year, 9, Weekday::Mon, 1), "Labour Day"),
+        holiday(nth_weekday(year, 10, Weekday::Mon, 2), "Thanksgiving"),
+        holiday(christmas, "Christmas"),
+        holiday(boxing_day, "Boxing Day"),
+    ];
+    if year >= 2008 {
+        holidays.push(holiday(nth_weekday(year, 2, Weekday::Mon, 3), "Family Day"));
+    }
+    holidays
+}
+
+/// Christmas and Boxing Day as taken in the UK and Canada, moved to the next
+/// weekdays when they fall on a weekend
+fn christmas_and_boxing_day(year: i32) -> (NaiveDate, NaiveDate) {
+    let christmas = ymd(year, 12, 25);
+    let days = Duration::days;
+    match christmas.weekday() {
+        Weekday::Fri => (christmas, christmas + days(3)),
+        Weekday::Sat => (christmas + days(2), christmas + days(3)),
+        Weekday::Sun => (christmas + days(2), christmas + days(1)),
+        _ => (christmas, christmas + days(1)),
+    }
+}
+
+/// Holidays on a weekend are taken on the Monday after
+fn next_monday(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date + Duration::days(2),
+        Weekday::Sun => date + Duration::days(1),
+        _ => date,
+    }
+}
+
+/// Holidays on a Saturday are taken on the Friday, on a Sunday the Monday
+fn observed(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date - Duration::days(1),
+        Weekday::Sun => date + Duration::days(1),
+        _ => date,
+    }
+}
+
+/// Tuesday to Friday, when the day before is a trading day
+fn is_midweek(date: NaiveDate) -> bool {
+    matches!(
+        date.weekday(),
+        Weekday::Tue | Weekday::Wed | Weekday::Thu | Weekday::Fri
+    )
+}
+
+/// The `n`th `weekday` of a month, counting from one
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u32) -> NaiveDate {
+    let first = ymd(year, month, 1);
+    let offset = (7 + weekday.num_days_from_monday() - first.weekday().num_days_from_monday()) % 7;
+    first + Duration::days((offset + 7 * (n - 1)) as i64)
+}
+
+fn last_weekday(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    let next_month = if month == 12 {
+        ymd(year + 1, 1, 1)
+    } else {
+        ymd(year, month + 1, 1)
+    };
+    let last = next_month - Duration::days(1);
+    let offset = (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    last - Duration::days(offset as i64)
+}
+
+/// Easter Sunday in the Gregorian calendar
+fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    ymd(year, month as u32, day as u32)
+}
+
+/// Only called with dates that exist
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("valid date")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn us_session(now: DateTime<Utc>) -> Session {
+        Market::for_stock("AAPL", Some("US")).status(now).session
+    }
+
+    fn holiday(date: NaiveDate) -> Option<Holiday> {
+        us_holidays(date.year())
+            .into_iter()
+            .find(|holiday| holiday.date == date)
+    }
+
+    #[test]
+    fn easter_sundays() {
+        assert_eq!(easter(2019), ymd(2019, 4, 21));
+        assert_eq!(easter(2024), ymd(2024, 3, 31));
+        assert_eq!(easter(2025), ymd(2025, 4, 20));
+    }
+
+    #[test]
+    fn good_friday() {
+        let good_friday = holiday(ymd(2024, 3, 29)).unwrap();
+        assert_eq!(good_friday.name, "Good Friday");
+        assert_eq!(good_friday.early_close, None);
+    }
+
+    #[test]
+    fn monday_holidays() {
+        assert_eq!(nth_weekday(2024, 1, Weekday::Mon, 3), ymd(2024, 1, 15));
+        assert_eq!(nth_weekday(2024, 9, Weekday::Mon, 1), ymd(2024, 9, 2));
+        assert_eq!(last_weekday(2024, 5, Weekday::Mon), ymd(2024, 5, 27));
+        assert_eq!(last_weekday(2021, 5, Weekday::Mon), ymd(2021, 5, 31));
+        assert_eq!(nth_weekday(2024, 11, Weekday::Thu, 4), ymd(2024, 11, 28));
+    }
+
+    #[test]
+    fn weekend_holidays_are_observed() {
+        // Juneteenth 2027 is a Saturday, Christmas 2022 a Sunday
+        assert_eq!(holiday(ymd(2027, 6, 18)).unwrap().name, "Juneteenth");
+        assert!(holiday(ymd(2027, 6, 19)).is_none());
+        assert_eq!(holiday(ymd(2022, 12, 26)).unwrap().name, "Christmas");
+        assert_eq!(observed(ymd(2026, 7, 4)), ymd(2026, 7, 3));
+    }
+
+    #[test]
+    fn new_year_on_a_saturday_is_not_observed() {
+        assert!(holiday(ymd(2021, 12, 31)).is_none());
+        assert!(us_holidays(2022)
+            .iter()
+            .all(|holiday| holiday.name != "New Year's Day"));
+        assert_eq!(us_session(at(2021, 12, 31, 15, 0)), Session::Open);
+    }
+
+    #[test]
+    fn juneteenth_only_from_2022() {
+        assert!(us_holidays(2021)
+            .iter()
+            .all(|holiday| holiday.name != "Juneteenth"));
+        assert_eq!(holiday(ymd(2024, 6, 19)).unwrap().name, "Juneteenth");
+    }
+
+    #[test]
+    fn early_closes() {
+        let christmas_eve = holiday(ymd(2024, 12, 24)).unwrap();
+        assert_eq!(christmas_eve.early_close, Some(13 * 60));
+        let black_friday = holiday(ymd(2024, 11, 29)).unwrap();
+        assert_eq!(black_friday.early_close, Some(13 * 60));
+        // Christmas 2022 falls on a Sunday, so the Friday before is a full day
+        assert!(holiday(ymd(2022, 12, 23)).is_none());
+
+        // 12:59 and 13:00 in New York
+        assert_eq!(us_session(at(2024, 12, 24, 17, 59)), Session::Open);
+        assert_eq!(us_session(at(2024, 12, 24, 18, 0)), Session::AfterHours);
+        assert_eq!(us_session(at(2024, 12, 24, 22, 0)), Session::Closed);
+    }
+
+    #[test]
+    fn closed_on_holidays_and_weekends() {
+        let status = Market::for_stock("AAPL", None).status(at(2024, 11, 28, 15, 0));
+        assert_eq!(status.session, Session::Closed);
+        assert_eq!(status.holiday, Some("Thanksgiving"));
+        assert_eq!(status.label(), "Closed (Thanksgiving)");
+        assert_eq!(us_session(at(2024, 3, 16, 15, 0)), Session::Closed);
+        assert_eq!(us_session(at(2024, 3, 17, 15, 0)), Session::Closed);
+    }
+
+    #[test]
+    fn extended_hours_boundaries() {
+        // New York is on daylight saving time, four hours behind UTC
+        assert_eq!(us_session(at(2024, 3, 12, 7, 59)), Session::Closed);
+        assert_eq!(us_session(at(2024, 3, 12, 8, 0)), Session::PreMarket);
+        assert_eq!(us_session(at(2024, 3, 12, 13, 29)), Session::PreMarket);
+        assert_eq!(us_session(at(2024, 3, 12, 13, 30)), Session::Open);
+        assert_eq!(us_session(at(2024, 3, 12, 19, 59)), Session::Open);
+        assert_eq!(us_session(at(2024, 3, 12, 20, 0)), Session::AfterHours);
+        assert_eq!(us_session(at(2024, 3, 12, 23, 59)), Session::AfterHours);
+        assert_eq!(us_session(at(2024, 3, 13, 0, 0)), Session::Closed);
+        // and five hours behind in winter
+        assert_eq!(us_session(at(2024, 3, 8, 14, 29)), Session::PreMarket);
+        assert_eq!(us_session(at(2024, 3, 8, 14, 30)), Session::Open);
+    }
+
+    #[test]
+    fn lunch_breaks() {
+        let hong_kong = Market::for_stock("0700.HK", None);
+        // 11:59, 12:00 and 13:00 in Hong Kong
+        assert_eq!(
+            hong_kong.status(at(2024, 3, 12, 3, 59)).session,
+            Session::Open
+        );
+        assert_eq!(
+            hong_kong.status(at(2024, 3, 12, 4, 0)).session,
+            Session::Lunch
+        );
+        assert_eq!(
+            hong_kong.status(at(2024, 3, 12, 5, 0)).session,
+            Session::Open
+        );
+        let tokyo = Market::for_stock("7203.T", None);
+        // 11:30 in Tokyo
+        assert_eq!(tokyo.status(at(2024, 3, 12, 2, 30)).session, Session::Lunch);
+    }
+
+    fn closed_for(symbol: &str, now: DateTime<Utc>) -> Option<&'static str> {
+        let status = Market::for_stock(symbol, None).status(now);
+        assert!(status.holidays_known);
+        match status.session {
+            Session::Closed => status.holiday,
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn european_holidays() {
+        assert_eq!(
+            closed_for("VOD.L", at(2024, 4, 1, 12, 0)),
+            Some("Easter Monday")
+        );
+        assert_eq!(
+            closed_for("VOD.L", at(2024, 8, 26, 12, 0)),
+            Some("Summer bank holiday")
+        );
+        // Boxing Day 2020 was a Saturday, so the Monday after is off
+        assert_eq!(
+            closed_for("VOD.L", at(2020, 12, 28, 12, 0)),
+            Some("Boxing Day")
+        );
+        assert_eq!(
+            closed_for("SAP.DE", at(2024, 5, 1, 10, 0)),
+            Some("Labour Day")
+        );
+        assert_eq!(
+            closed_for("SAP.DE", at(2024, 12, 24, 10, 0)),
+            Some("Christmas Eve")
+        );
+        // Xetra trades on Whit Monday
+        assert_eq!(closed_for("SAP.DE", at(2024, 5, 20, 10, 0)), None);
+    }
+
+    #[test]
+    fn lse_closes_early_on_christmas_eve() {
+        let london = Market::for_stock("VOD.L", None);
+        assert_eq!(
+            london.status(at(2024, 12, 24, 12, 29)).session,
+            Session::Open
+        );
+        assert_eq!(
+            london.status(at(2024, 12, 24, 12, 30)).session,
+            Session::Closed
+        );
+        assert_eq!(
+            london.status(at(2024, 12, 23, 16, 29)).session,
+            Session::Open
+        );
+    }
+
+    #[test]
+    fn canadian_holidays() {
+        assert_eq!(
+            closed_for("RY.TO", at(2024, 2, 19, 15, 0)),
+            Some("Family Day")
+        );
+        assert_eq!(
+            closed_for("RY.TO", at(2024, 5, 20, 15, 0)),
+            Some("Victoria Day")
+        );
+        // Canada Day 2023 was a Saturday
+        assert_eq!(
+            closed_for("RY.TO", at(2023, 7, 3, 15, 0)),
+            Some("Canada Day")
+        );
+        assert_eq!(
+            closed_for("RY.TO", at(2024, 10, 14, 15, 0)),
+            Some("Thanksgiving")
+        );
+        assert_eq!(closed_for("RY.TO", at(2024, 11, 11, 15, 0)), None);
+        // Victoria Day is the Monday before May 25, even on May 24
+        assert_eq!(
+            closed_for("RY.TO", at(2021, 5, 24, 15, 0)),
+            Some("Victoria Day")
+        );
+    }
+
+    #[test]
+    fn asian_holidays_are_unknown() {
+        let hong_kong = Market::for_stock("0700.HK", None);
+        let weekday = hong_kong.status(at(2024, 3, 12, 2, 0));
+        assert_eq!(weekday.session, Session::Open);
+        assert_eq!(weekday.label(), "Open (holidays unknown)");
+        // Weekends are closed either way
+        let weekend = hong_kong.status(at(2024, 3, 16, 2, 0));
+        assert_eq!(weekend.label(), "Closed");
+    }
+
+    #[test]
+    fn exchange_from_code_then_suffix() {
+        assert_eq!(Market::for_stock("VOD.L", Some("L")).name(), "LSE");
+        assert_eq!(Market::for_stock("VOD.L", Some("LSE")).name(), "LSE");
+        assert_eq!(Market::for_stock("VOD.L", None).name(), "LSE");
+        assert_eq!(Market::for_stock("BRK.B", None).name(), "NYSE");
+        assert_eq!(Market::for_stock("AAPL", None).name(), "NYSE");
+    }
+
+    #[test]
+    fn forex_trades_sunday_to_friday() {
+        let status = |now| Market::Forex.status(now).session;
+        // 16:59 and 17:00 in New York
+        assert_eq!(status(at(2024, 3, 15, 20, 59)), Session::Open);
+        assert_eq!(status(at(2024, 3, 15, 21, 0)), Session::Closed);
+        assert_eq!(status(at(2024, 3, 17, 20, 59)), Session::Closed);
+        assert_eq!(status(at(2024, 3, 17, 21, 0)), Session::Open);
+    }
+}
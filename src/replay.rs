@@ -0,0 +1,191 @@
+use crate::asset::Feed;
+use crate::error::StonksError;
+use crate::subscription::{ConnectionStatus, FeedEvent};
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use mpsc::Sender;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+/// A raw websocket frame and the time it was received, one per line in a
+/// recording.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RecordedFrame {
+    /// Milliseconds since the unix epoch
+    pub received: i64,
+    pub frame: String,
+}
+
+/// Appends every raw frame from the live feed to a file so it can be replayed
+/// later.
+pub struct FeedRecorder {
+    writer: BufWriter<File>,
+}
+
+impl FeedRecorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<FeedRecorder> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FeedRecorder {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, frame: &str) -> io::Result<()> {
+        let recorded = RecordedFrame {
+            received: Utc::now().timestamp_millis(),
+            frame: frame.to_string(),
+        };
+        serde_json::to_writer(&mut self.writer, &recorded)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// How fast to play back a recording
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Multiple of the recorded pace, e.g. 10.0 for 10x
+    Times(f64),
+    /// No delay between frames
+    Max,
+}
+
+impl ReplaySpeed {
+    /// Parses `1`, `10x` or `max`
+    pub fn parse(speed: &str) -> Option<ReplaySpeed> {
+        let speed = speed.trim().to_lowercase();
+        if speed == "max" {
+            return Some(ReplaySpeed::Max);
+        }
+        match speed.trim_end_matches('x').parse::<f64>() {
+            Ok(times) if times > 0.0 => Some(ReplaySpeed::Times(times)),
+            _ => None,
+        }
+    }
+
+    fn delay(&self, elapsed_ms: i64) -> Duration {
+        match self {
+            ReplaySpeed::Max => Duration::from_millis(0),
+            ReplaySpeed::Times(times) => {
+                Duration::from_millis((elapsed_ms.max(0) as f64 / times) as u64)
+            }
+        }
+    }
+}
+
+/// Feeds a recording back through the live price channel, keeping the gaps
+/// between frames scaled by `speed`.
+pub fn replay<P: AsRef<Path>>(
+    path: P,
+    speed: ReplaySpeed,
+    tx: Sender<FeedEvent>,
+) -> Result<(), StonksError> {
+    let reader = BufReader::new(File::open(&path)?);
+    info!(
+        "Replaying live feed from {} at {:?}",
+        path.as_ref().display(),
+        speed
+    );
+
+    let _ = tx.send(FeedEvent::Status(ConnectionStatus::Connected));
+    let mut previous: Option<i64> = None;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recorded: RecordedFrame = serde_json::from_str(&line)?;
+        if let Some(previous) = previous {
+            thread::sleep(speed.delay(recorded.received - previous));
+        }
+        previous = Some(recorded.received);
+        match Feed::parse(&recorded.frame) {
+            Ok(feed) => {
+                let trades = feed.trades();
+                if !trades.is_empty() && tx.send(FeedEvent::Trades(trades)).is_err() {
+                    return Ok(());
+                }
+            }
+            Err(err) => warn!("Skipping recorded frame {}: {}", recorded.frame, err),
+        }
+    }
+    info!("Replay finished");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_replay_speeds() {
+        assert_eq!(ReplaySpeed::parse("1"), Some(ReplaySpeed::Times(1.0)));
+        assert_eq!(ReplaySpeed::parse(" 10x "), Some(ReplaySpeed::Times(10.0)));
+        assert_eq!(ReplaySpeed::parse("0.5X"), Some(ReplaySpeed::Times(0.5)));
+        assert_eq!(ReplaySpeed::parse("MAX"), Some(ReplaySpeed::Max));
+        assert_eq!(ReplaySpeed::parse("0"), None);
+        assert_eq!(ReplaySpeed::parse("-2x"), None);
+        assert_eq!(ReplaySpeed::parse("fast"), None);
+        assert_eq!(ReplaySpeed::parse(""), None);
+    }
+
+    #[test]
+    fn delay_scales_with_speed() {
+        assert_eq!(
+            ReplaySpeed::Times(2.0).delay(1000),
+            Duration::from_millis(500)
+        );
+        assert_eq!(ReplaySpeed::Times(1.0).delay(-5), Duration::from_millis(0));
+        assert_eq!(ReplaySpeed::Max.delay(1000), Duration::from_millis(0));
+    }
+
+    #[test]
+    fn replays_recorded_trades() {
+        let path = std::env::temp_dir().join(format!("stonks-replay-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut recorder = FeedRecorder::create(&path).unwrap();
+        recorder
+            .record(r#"{"data":[{"p":10.5,"s":"AAPL","t":1000,"v":2}],"type":"trade"}"#)
+            .unwrap();
+        recorder.record(r#"{"type":"ping"}"#).unwrap();
+        recorder.record("not json").unwrap();
+        recorder
+            .record(r#"{"data":[{"p":11.0,"s":"MSFT","t":2000,"v":1}],"type":"trade"}"#)
+            .unwrap();
+        drop(recorder);
+
+        let (tx, rx) = mpsc::channel();
+        replay(&path, ReplaySpeed::Max, tx).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let events: Vec<FeedEvent> = rx.iter().collect();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            events[0],
+            FeedEvent::Status(ConnectionStatus::Connected)
+        ));
+        let symbols: Vec<Vec<String>> = events[1..]
+            .iter()
+            .map(|event| match event {
+                FeedEvent::Trades(trades) => trades.iter().map(|t| t.symbol.clone()).collect(),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(symbols, vec![vec!["AAPL"], vec!["MSFT"]]);
+    }
+
+    #[test]
+    fn missing_recording_is_an_error() {
+        let (tx, _rx) = mpsc::channel();
+        let path = std::env::temp_dir().join("stonks-replay-missing.jsonl");
+        assert!(replay(path, ReplaySpeed::Max, tx).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use reqwest::header::HeaderMap;
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Calls per minute allowed on the finnhub.io free plan
+pub const FREE_TIER_CALLS_PER_MINUTE: usize = 60;
+/// How many times a rate limited request is retried before giving up
+pub const MAX_RETRIES: u32 = 5;
+
+const WINDOW: Duration = Duration::from_secs(60);
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// API calls left in the current minute
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub remaining: usize,
+    pub limit: usize,
+}
+
+/// Keeps every request to the API under the per-minute quota. Requests wait in
+/// `acquire` until a slot is free, and a 429 pauses all of them until the
+/// server is ready again.
+pub struct RequestScheduler {
+    limit: usize,
+    state: Mutex<SchedulerState>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    /// When each call in the last minute was made, oldest first
+    calls: VecDeque<Instant>,
+    /// Set after a 429, nothing is sent before then
+    blocked_until: Option<Instant>,
+    /// Last `X-Ratelimit-Remaining` reported by the server
+    server_remaining: Option<usize>,
+}
+
+impl RequestScheduler {
+    pub fn new(limit: usize) -> RequestScheduler {
+        RequestScheduler {
+            limit: limit.max(1),
+            state: Mutex::new(SchedulerState::default()),
+        }
+    }
+
+    /// Waits until a request may be sent and counts it against the quota
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                match state.wait(Instant::now(), self.limit) {
+                    Some(wait) => wait,
+                    None => return,
+                }
+            };
+            debug!("Waiting {:?} for API quota", wait);
+            tokio::time::delay_for(wait).await;
+        }
+    }
+
+    /// Pauses all requests after a 429 for `retry_after` if the server gave
+    /// one, otherwise for an exponential backoff based on `attempt`. Returns
+    /// the pause.
+    pub fn rate_limited(&self, retry_after: Option<Duration>, attempt: u32) -> Duration {
+        let backoff = backoff(attempt);
+        let delay = retry_after.map_or(backoff, |retry_after| retry_after.max(backoff));
+
+        let mut state = self.state.lock().unwrap();
+        let until = Instant::now() + delay;
+        if state.blocked_until.is_none_or(|blocked| blocked < until) {
+            state.blocked_until = Some(until);
+        }
+        state.server_remaining = Some(0);
+        delay
+    }
+
+    /// Keeps track of the quota reported in a response's headers
+    pub fn update_from_headers(&self, headers: &HeaderMap) {
+        let remaining = headers
+            .get("X-Ratelimit-Remaining")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        if remaining.is_some() {
+            self.state.lock().unwrap().server_remaining = remaining;
+        }
+    }
+
+    pub fn quota(&self) -> Quota {
+        let mut state = self.state.lock().unwrap();
+        state.prune(Instant::now());
+        let mut remaining = self.limit.saturating_sub(state.calls.len());
+        if let Some(server_remaining) = state.server_remaining {
+            remaining = remaining.min(server_remaining);
+        }
+        Quota {
+            remaining,
+            limit: self.limit,
+        }
+    }
+}
+
+impl SchedulerState {
+    /// How long to wait before the next call, or `None` after counting it
+    fn wait(&mut self, now: Instant, limit: usize) -> Option<Duration> {
+        self.prune(now);
+        if let Some(until) = self.blocked_until.filter(|until| *until > now) {
+            return Some(until - now);
+        }
+        self.blocked_until = None;
+        // The server's count only drops once our oldest call leaves the window
+        let exhausted = self.calls.len() >= limit || self.server_remaining == Some(0);
+        match self.calls.front() {
+            Some(oldest) if exhausted => Some(*oldest + WINDOW - now),
+            _ => {
+                self.calls.push_back(now);
+                None
+            }
+        }
+    }
+
+    /// Forgets calls that are out of the window
+    fn prune(&mut self, now: Instant) {
+        let before = self.calls.len();
+        while let Some(call) = self.calls.front() {
+            if now.duration_since(*call) >= WINDOW {
+                self.calls.pop_front();
+            } else {
+                break;
+            }
+        }
+        if before > 0 && self.calls.is_empty() {
+            // A full window has passed, the server's count has reset too
+            self.server_remaining = None;
+        }
+    }
+}
+
+/// Exponential delay before retry number `attempt`, starting from zero
+pub fn backoff(attempt: u32) -> Duration {
+    BASE_BACKOFF
+        .checked_mul(2u32.saturating_pow(attempt))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+/// Seconds to wait from a `Retry-After` header
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get("Retry-After")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[tokio::test]
+    async fn acquire_counts_against_the_quota() {
+        let scheduler = RequestScheduler::new(3);
+        scheduler.acquire().await;
+        scheduler.acquire().await;
+        assert_eq!(
+            scheduler.quota(),
+            Quota {
+                remaining: 1,
+                limit: 3
+            }
+        );
+    }
+
+    #[test]
+    fn quota_refills_after_a_minute() {
+        let start = Instant::now();
+        let mut state = SchedulerState::default();
+        state.calls.push_back(start);
+        state.calls.push_back(start + Duration::from_secs(30));
+        state.server_remaining = Some(0);
+
+        state.prune(start + Duration::from_secs(59));
+        assert_eq!(state.calls.len(), 2);
+        state.prune(start + WINDOW);
+        assert_eq!(state.calls.len(), 1);
+        assert_eq!(state.server_remaining, Some(0));
+        state.prune(start + Duration::from_secs(90));
+        assert!(state.calls.is_empty());
+        assert_eq!(state.server_remaining, None);
+    }
+
+    #[test]
+    fn waits_for_the_window_when_the_server_quota_is_used_up() {
+        let start = Instant::now();
+        let mut state = SchedulerState::default();
+        assert_eq!(state.wait(start, 60), None);
+        state.server_remaining = Some(0);
+
+        let later = start + Duration::from_secs(20);
+        assert_eq!(state.wait(later, 60), Some(Duration::from_secs(40)));
+        assert_eq!(state.calls.len(), 1);
+        // Once the tracked call expires the server's count has reset
+        assert_eq!(state.wait(start + WINDOW, 60), None);
+        assert_eq!(state.server_remaining, None);
+        assert_eq!(state.calls.len(), 1);
+    }
+
+    #[test]
+    fn waits_for_the_oldest_call_when_the_quota_is_full() {
+        let start = Instant::now();
+        let mut state = SchedulerState::default();
+        assert_eq!(state.wait(start, 2), None);
+        assert_eq!(state.wait(start + Duration::from_secs(10), 2), None);
+        assert_eq!(
+            state.wait(start + Duration::from_secs(15), 2),
+            Some(Duration::from_secs(45))
+        );
+        state.blocked_until = Some(start + Duration::from_secs(100));
+        assert_eq!(
+            state.wait(start + Duration::from_secs(70), 2),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn server_remaining_caps_the_quota() {
+        let scheduler = RequestScheduler::new(60);
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Ratelimit-Remaining", HeaderValue::from_static("7"));
+        scheduler.update_from_headers(&headers);
+        assert_eq!(scheduler.quota().remaining, 7);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_minute() {
+        let scheduler = RequestScheduler::new(60);
+        let secs = Duration::from_secs;
+        assert_eq!(scheduler.rate_limited(None, 0), secs(1));
+        assert_eq!(scheduler.rate_limited(None, 1), secs(2));
+        assert_eq!(scheduler.rate_limited(None, 5), secs(32));
+        assert_eq!(scheduler.rate_limited(None, 6), MAX_BACKOFF);
+        assert_eq!(scheduler.rate_limited(None, 40), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn rate_limited_waits_for_retry_after_or_backoff() {
+        let scheduler = RequestScheduler::new(60);
+        let secs = Duration::from_secs;
+        assert_eq!(scheduler.rate_limited(Some(secs(10)), 0), secs(10));
+        assert_eq!(scheduler.rate_limited(Some(secs(1)), 3), secs(8));
+        assert_eq!(scheduler.rate_limited(None, 2), secs(4));
+        assert_eq!(scheduler.quota().remaining, 0);
+
+        let state = scheduler.state.lock().unwrap();
+        let blocked = state.blocked_until.unwrap() - Instant::now();
+        // The longest pause wins
+        assert!(blocked > secs(8) && blocked <= secs(10));
+    }
+
+    #[test]
+    fn retry_after_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("Retry-After", HeaderValue::from_static(" 30 "));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(30)));
+        headers.insert(
+            "Retry-After",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+}
@@ -1,5 +1,3 @@
-use crate::app::{App, InputMode};
-use crate::asset;
 use crate::asset::Stock;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
@@ -17,6 +15,11 @@ impl SearchEngine {
             engine: fill_engine(stocks),
         }
     }
+
+    /// Best matches for `query`, at most `count` of them
+    pub fn suggestions(&self, query: &str, count: usize) -> Vec<Stock> {
+        self.engine.search(query).into_iter().take(count).collect()
+    }
 }
 
 fn fill_engine(stocks: &mut Vec<Stock>) -> SimSearch<Stock> {
@@ -26,5 +29,3 @@ fn fill_engine(stocks: &mut Vec<Stock>) -> SimSearch<Stock> {
     }
     engine
 }
-
-pub fn search(query: &str) {}
@@ -0,0 +1,568 @@
+use crate::asset::{
+    timestamp_to_date, Analysts, AssetClass, ChartRange, CompanyInfo, CorporateActions, Earnings,
+    EarningsRelease, ForexRates, Fundamentals, InsiderTransaction, MarketDataProvider, NewsArticle,
+    Peer, Prices, Quote, Stock,
+};
+use crate::error::StonksError;
+use crate::scheduler::Quota;
+use chrono::{Duration, Utc};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::{
+    cmp::Reverse,
+    sync::{mpsc::Sender, Arc},
+    thread,
+};
+use tokio::{runtime::Runtime, sync::mpsc};
+
+/// Days of company news to fetch
+const NEWS_DAYS: i64 = 7;
+/// Days of past earnings reports to mark on the chart
+const EARNINGS_PAST_DAYS: i64 = 2 * 365;
+/// Days ahead to look for upcoming earnings reports
+const EARNINGS_AHEAD_DAYS: i64 = 90;
+/// Days of insider transactions to fetch
+const INSIDER_DAYS: i64 = 365;
+/// Peers that get a quote, each one costs an API call
+const PEER_QUOTES: usize = 10;
+
+/// Data the UI asks the service for
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+pub enum DataCommand {
+    /// Every exchange of an asset class, to load the securities of
+    LoadExchanges { asset_class: AssetClass },
+    /// The list of securities on an exchange, to search
+    LoadSecurities {
+        asset_class: AssetClass,
+        exchange: String,
+    },
+    /// Profile and chart for a newly selected symbol
+    LoadCompany {
+        symbol: String,
+        asset_class: AssetClass,
+        range: ChartRange,
+    },
+    /// Quote and 52 week range of the current symbol
+    LoadQuote {
+        symbol: String,
+        asset_class: AssetClass,
+    },
+    /// Recent headlines about the current symbol
+    LoadNews {
+        symbol: String,
+        asset_class: AssetClass,
+    },
+    /// Earnings reports and surprises of the current company
+    LoadEarnings { symbol: String },
+    /// Upcoming earnings reports of the watched companies
+    LoadEarningsCalendar { symbols: Vec<String> },
+    /// Analyst ratings and price targets of the current company
+    LoadAnalysts { symbol: String },
+    /// Dividends and splits of the current company
+    LoadCorporateActions { symbol: String },
+    /// Last year of insider transactions in the current company
+    LoadInsiders { symbol: String },
+    /// Peers of the current company and their quotes
+    LoadPeers { symbol: String },
+    /// Key metrics of the current company
+    LoadFundamentals { symbol: String },
+    /// Exchange rates against a base currency
+    LoadRates { base: String },
+    /// Chart for a new range of the current symbol
+    LoadPrices {
+        symbol: String,
+        asset_class: AssetClass,
+        range: ChartRange,
+    },
+}
+
+/// Results of a `DataCommand`, posted back to the UI's event loop
+#[derive(Debug)]
+pub enum DataEvent {
+    Exchanges {
+        asset_class: AssetClass,
+        result: Result<Vec<String>, StonksError>,
+    },
+    Securities {
+        asset_class: AssetClass,
+        exchange: String,
+        result: Result<Vec<Stock>, StonksError>,
+    },
+    Company {
+        symbol: String,
+        result: Result<CompanyInfo, StonksError>,
+    },
+    Quote {
+        symbol: String,
+        result: Result<Quote, StonksError>,
+    },
+    News {
+        symbol: String,
+        result: Result<Vec<NewsArticle>, StonksError>,
+    },
+    Earnings {
+        symbol: String,
+        result: Result<Earnings, StonksError>,
+    },
+    EarningsCalendar {
+        result: Result<Vec<EarningsRelease>, StonksError>,
+    },
+    Analysts {
+        symbol: String,
+        result: Result<Analysts, StonksError>,
+    },
+    CorporateActions {
+        symbol: String,
+        result: Result<CorporateActions, StonksError>,
+    },
+    Insiders {
+        symbol: String,
+        result: Result<Vec<InsiderTransaction>, StonksError>,
+    },
+    Peers {
+        symbol: String,
+        result: Result<Vec<Peer>, StonksError>,
+    },
+    Fundamentals {
+        symbol: String,
+        result: Result<Fundamentals, StonksError>,
+    },
+    Rates {
+        base: String,
+        result: Result<ForexRates, StonksError>,
+    },
+    Prices {
+        symbol: String,
+        range: ChartRange,
+        result: Result<Prices, StonksError>,
+    },
+}
+
+/// Handle to the background thread that does all REST requests. Commands are
+/// run concurrently on one long-lived tokio runtime and each result comes back
+/// as an event, so the UI never waits on the network.
+#[derive(Clone)]
+pub struct DataService {
+    commands: mpsc::UnboundedSender<DataCommand>,
+    provider: Arc<dyn MarketDataProvider>,
+}
+
+impl DataService {
+    /// Starts the runtime thread. Results are sent on `events`, wrapped in
+    /// whatever event type the receiving loop uses.
+    pub fn spawn<E>(provider: Arc<dyn MarketDataProvider>, events: Sender<E>) -> DataService
+    where
+        E: From<DataEvent> + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel::<DataCommand>();
+        let service_provider = provider.clone();
+        thread::spawn(move || {
+            let mut runtime = Runtime::new().expect("Could not start tokio runtime");
+            runtime.block_on(async move {
+                while let Some(command) = rx.recv().await {
+                    debug!("Data command {:?}", command);
+                    let provider = service_provider.clone();
+                    let events = events.clone();
+                    tokio::spawn(async move {
+                        let event = run(provider.as_ref(), command).await;
+                        // Fails only once the UI has quit
+                        let _ = events.send(E::from(event));
+                    });
+                }
+            });
+        });
+        DataService {
+            commands: tx,
+            provider,
+        }
+    }
+
+    pub fn send(&self, command: DataCommand) {
+        if let Err(err) = self.commands.send(command) {
+            error!("Data service stopped: {}", err);
+        }
+    }
+
+    pub fn quota(&self) -> Option<Quota> {
+        self.provider.quota()
+    }
+}
+
+async fn run(provider: &dyn MarketDataProvider, command: DataCommand) -> DataEvent {
+    match command {
+        DataCommand::LoadExchanges { asset_class } => {
+            let result = provider.exchanges(asset_class).await;
+            DataEvent::Exchanges {
+                asset_class,
+                result,
+            }
+        }
+        DataCommand::LoadSecurities {
+            asset_class,
+            exchange,
+        } => {
+            let result = provider
+                .securities(asset_class, &exchange)
+                .await
+                .map(|mut securities| {
+                    for stock in &mut securities {
+                        stock.exchange = exchange.clone();
+                        stock.asset_class = asset_class;
+                    }
+                    securities
+                });
+            DataEvent::Securities {
+                asset_class,
+                exchange,
+                result,
+            }
+        }
+        DataCommand::LoadCompany {
+            symbol,
+            asset_class,
+            range,
+        } => {
+            let result = load_company(provider, &symbol, asset_class, range).await;
+            DataEvent::Company { symbol, result }
+        }
+        DataCommand::LoadQuote {
+            symbol,
+            asset_class,
+        } => {
+            let result = load_quote(provider, &symbol, asset_class).await;
+            DataEvent::Quote { symbol, result }
+        }
+        DataCommand::LoadNews {
+            symbol,
+            asset_class,
+        } => {
+            let result = load_news(provider, &symbol, asset_class).await;
+            DataEvent::News { symbol, result }
+        }
+        DataCommand::LoadEarnings { symbol } => {
+            let result = load_earnings(provider, &symbol).await;
+            DataEvent::Earnings { symbol, result }
+        }
+        DataCommand::LoadEarningsCalendar { symbols } => {
+            let result = load_earnings_calendar(provider, &symbols).await;
+            DataEvent::EarningsCalendar { result }
+        }
+        DataCommand::LoadAnalysts { symbol } => {
+            let result = load_analysts(provider, &symbol).await;
+            DataEvent::Analysts { symbol, result }
+        }
+        DataCommand::LoadCorporateActions { symbol } => {
+            let result = load_corporate_actions(provider, &symbol).await;
+            DataEvent::CorporateActions { symbol, result }
+        }
+        DataCommand::LoadInsiders { symbol } => {
+            let result = load_insiders(provider, &symbol).await;
+            DataEvent::Insiders { symbol, result }
+        }
+        DataCommand::LoadPeers { symbol } => {
+            let result = load_peers(provider, &symbol).await;
+            DataEvent::Peers { symbol, result }
+        }
+        DataCommand::LoadFundamentals { symbol } => {
+            let result = provider.fundamentals(&symbol).await;
+            DataEvent::Fundamentals { symbol, result }
+        }
+        DataCommand::LoadRates { base } => {
+            let result = provider.forex_rates(&base).await;
+            DataEvent::Rates { base, result }
+        }
+        DataCommand::LoadPrices {
+            symbol,
+            asset_class,
+            range,
+        } => {
+            let result = load_prices(provider, &symbol, asset_class, range).await;
+            DataEvent::Prices {
+                symbol,
+                range,
+                result,
+            }
+        }
+    }
+}
+
+async fn load_company(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+    asset_class: AssetClass,
+    range: ChartRange,
+) -> Result<CompanyInfo, StonksError> {
+    let mut company = match asset_class {
+        AssetClass::Stock => provider.company_profile(symbol).await?,
+        _ => CompanyInfo::pair(symbol, asset_class),
+    };
+    company.prices = load_prices(provider, symbol, asset_class, range).await?;
+    Ok(company)
+}
+
+/// The quote endpoint only covers stocks, pairs are quoted from their last
+/// daily bar
+async fn load_quote(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+    asset_class: AssetClass,
+) -> Result<Quote, StonksError> {
+    let year = load_prices(provider, symbol, asset_class, ChartRange::OneYear).await;
+    let mut quote = match (asset_class, &year) {
+        (AssetClass::Stock, _) => provider.quote(symbol).await?,
+        (_, Ok(year)) => Quote::from_candles(year).ok_or(StonksError::NoData)?,
+        (_, Err(err)) => return Err(err.clone()),
+    };
+    match year {
+        Ok(year) => quote.set_year_range(&year),
+        Err(err) => warn!("No 52 week range for {}: {}", symbol, err),
+    }
+    Ok(quote)
+}
+
+/// A week of company news for stocks, the latest market news for pairs.
+/// Newest first.
+async fn load_news(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+    asset_class: AssetClass,
+) -> Result<Vec<NewsArticle>, StonksError> {
+    let mut news = match asset_class.news_category() {
+        Some(category) => provider.market_news(category).await?,
+        None => {
+            let today = Utc::now().date_naive();
+            let from = (today - Duration::days(NEWS_DAYS)).format("%Y-%m-%d");
+            let to = today.format("%Y-%m-%d");
+            provider
+                .company_news(symbol, &from.to_string(), &to.to_string())
+                .await?
+        }
+    };
+    news.sort_by_key(|article| Reverse(article.datetime));
+    Ok(news)
+}
+
+/// Reports from two years back up to the next quarter, and the surprises of
+/// the last quarters
+async fn load_earnings(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+) -> Result<Earnings, StonksError> {
+    let (from, to) = earnings_window();
+    let mut releases = provider.earnings_calendar(symbol, &from, &to).await?;
+    releases.sort_by(|a, b| a.date.cmp(&b.date));
+    let mut surprises = match provider.earnings_surprises(symbol).await {
+        Ok(surprises) => surprises,
+        // The calendar is still worth showing
+        Err(err) => {
+            warn!("No earnings surprises for {}: {}", symbol, err);
+            Vec::new()
+        }
+    };
+    surprises.sort_by(|a, b| b.period.cmp(&a.period));
+    Ok(Earnings {
+        releases,
+        surprises,
+    })
+}
+
+/// Dates `(from, to)` of every earnings calendar fetched. The company's
+/// reports and the watchlist's upcoming ones share it so they share a cache
+/// entry too.
+fn earnings_window() -> (String, String) {
+    let today = Utc::now().date_naive();
+    let from = today - Duration::days(EARNINGS_PAST_DAYS);
+    let to = today + Duration::days(EARNINGS_AHEAD_DAYS);
+    (
+        from.format("%Y-%m-%d").to_string(),
+        to.format("%Y-%m-%d").to_string(),
+    )
+}
+
+/// Upcoming reports of every symbol in `symbols`, soonest first. Symbols
+/// without a calendar are left out, it fails only if all of them fail.
+async fn load_earnings_calendar(
+    provider: &dyn MarketDataProvider,
+    symbols: &[String],
+) -> Result<Vec<EarningsRelease>, StonksError> {
+    let (from, to) = earnings_window();
+    let today = Utc::now().date_naive().format("%Y-%m-%d").to_string();
+    let mut releases = Vec::new();
+    let mut last_err = None;
+    let mut loaded = 0;
+    for symbol in symbols {
+        match provider.earnings_calendar(symbol, &from, &to).await {
+            Ok(calendar) => {
+                loaded += 1;
+                releases.extend(
+                    calendar
+                        .into_iter()
+                        .filter(|release| release.date >= today && release.date <= to),
+                );
+            }
+            Err(err) => {
+                warn!("No earnings calendar for {}: {}", symbol, err);
+                last_err = Some(err);
+            }
+        }
+    }
+    if let (0, Some(err)) = (loaded, last_err) {
+        return Err(err);
+    }
+    releases.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(releases)
+}
+
+/// Rating trends oldest month first, along with the price target if any
+/// analyst set one
+async fn load_analysts(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+) -> Result<Analysts, StonksError> {
+    let mut recommendations = provider.recommendations(symbol).await?;
+    recommendations.sort_by(|a, b| a.period.cmp(&b.period));
+    let price_target = match provider.price_target(symbol).await {
+        Ok(target) => Some(target),
+        Err(err) => {
+            warn!("No price target for {}: {}", symbol, err);
+            None
+        }
+    };
+    Ok(Analysts {
+        recommendations,
+        price_target,
+    })
+}
+
+/// Peers of `symbol` without itself, the first `PEER_QUOTES` with a quote.
+/// The rest only get prices from the live feed, and a peer without a quote is
+/// still listed.
+async fn load_peers(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+) -> Result<Vec<Peer>, StonksError> {
+    let mut peers = Vec::new();
+    for peer in provider.peers(symbol).await? {
+        if peer == symbol {
+            continue;
+        }
+        if peers.len() >= PEER_QUOTES {
+            peers.push(Peer {
+                symbol: peer,
+                quote: None,
+            });
+            continue;
+        }
+        let quote = match provider.quote(&peer).await {
+            Ok(quote) => Some(quote),
+            Err(err) => {
+                warn!("No quote for peer {}: {}", peer, err);
+                None
+            }
+        };
+        peers.push(Peer {
+            symbol: peer,
+            quote,
+        });
+    }
+    Ok(peers)
+}
+
+/// Every dividend and split as far back as the longest chart range goes.
+/// Splits are needed to adjust the chart, dividends are left out if they
+/// can't be loaded.
+async fn load_corporate_actions(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+) -> Result<CorporateActions, StonksError> {
+    let (from, to) = ChartRange::Max.window(Utc::now());
+    let from = timestamp_to_date(from).format("%Y-%m-%d").to_string();
+    let to = timestamp_to_date(to).format("%Y-%m-%d").to_string();
+    let mut splits = provider.splits(symbol, &from, &to).await?;
+    splits.sort_by(|a, b| a.date.cmp(&b.date));
+    let mut dividends = match provider.dividends(symbol, &from, &to).await {
+        Ok(dividends) => dividends,
+        Err(err) => {
+            warn!("No dividends for {}: {}", symbol, err);
+            Vec::new()
+        }
+    };
+    dividends.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(CorporateActions { dividends, splits })
+}
+
+/// A year of insider transactions, newest first
+async fn load_insiders(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+) -> Result<Vec<InsiderTransaction>, StonksError> {
+    let today = Utc::now().date_naive();
+    let from = (today - Duration::days(INSIDER_DAYS)).format("%Y-%m-%d");
+    let to = today.format("%Y-%m-%d");
+    let mut transactions = provider
+        .insider_transactions(symbol, &from.to_string(), &to.to_string())
+        .await?;
+    transactions.sort_by(|a, b| b.transaction_date.cmp(&a.transaction_date));
+    Ok(transactions)
+}
+
+async fn load_prices(
+    provider: &dyn MarketDataProvider,
+    symbol: &str,
+    asset_class: AssetClass,
+    range: ChartRange,
+) -> Result<Prices, StonksError> {
+    let (from, to) = range.window(Utc::now());
+    provider
+        .price_history(asset_class, symbol, range.resolution(), from, to)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::FixtureProvider;
+    use std::fs;
+
+    #[tokio::test]
+    async fn peers_leave_out_the_company() {
+        let provider = FixtureProvider::new("fixtures");
+        let peers = load_peers(&provider, "AAPL").await.unwrap();
+        let symbols: Vec<&str> = peers.iter().map(|peer| peer.symbol.as_str()).collect();
+        assert_eq!(symbols, vec!["MSFT", "TSLA", "DELL"]);
+        // Only TSLA has a quote fixture
+        assert!(peers[0].quote.is_none());
+        assert!(peers[1].quote.is_some());
+        assert!(peers[2].quote.is_none());
+    }
+
+    #[tokio::test]
+    async fn only_the_first_peers_get_quotes() {
+        let dir = std::env::temp_dir().join(format!("stonks-peers-{}", std::process::id()));
+        fs::create_dir_all(dir.join("peers")).unwrap();
+        fs::create_dir_all(dir.join("quote")).unwrap();
+        let symbols: Vec<String> = (0..PEER_QUOTES + 5).map(|i| format!("P{}", i)).collect();
+        fs::write(
+            dir.join("peers/BIG.json"),
+            serde_json::to_string(&symbols).unwrap(),
+        )
+        .unwrap();
+        for symbol in &symbols {
+            fs::copy(
+                "fixtures/quote/AAPL.json",
+                dir.join(format!("quote/{}.json", symbol)),
+            )
+            .unwrap();
+        }
+
+        let peers = load_peers(&FixtureProvider::new(&dir), "BIG")
+            .await
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(peers.len(), symbols.len());
+        let quoted = peers.iter().filter(|peer| peer.quote.is_some()).count();
+        assert_eq!(quoted, PEER_QUOTES);
+        assert!(peers[..PEER_QUOTES].iter().all(|peer| peer.quote.is_some()));
+    }
+}
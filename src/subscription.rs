@@ -0,0 +1,182 @@
+use crate::asset::LiveData;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use std::{collections::BTreeSet, sync::mpsc::Sender};
+
+/// Changes to the symbols streamed by the live feed
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedCommand {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+/// State of the live feed connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    /// The connection dropped, `attempt` is the retry being waited on
+    Reconnecting {
+        attempt: u32,
+    },
+    /// Not streaming and not trying to, e.g. without a live feed or API key
+    Offline,
+}
+
+/// What the live feed thread posts back to the UI
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedEvent {
+    /// All trades from one message of the feed
+    Trades(Vec<LiveData>),
+    Status(ConnectionStatus),
+}
+
+/// Keeps track of which symbols the live feed should stream: the selected
+/// symbol plus any watched ones, its peers and the pairs of a shown rate
+/// matrix. Tells the feed thread to subscribe or unsubscribe whenever that
+/// set changes.
+pub struct SubscriptionManager {
+    commands: Sender<FeedCommand>,
+    active: Option<String>,
+    watched: BTreeSet<String>,
+    peers: BTreeSet<String>,
+    rates: BTreeSet<String>,
+}
+
+impl SubscriptionManager {
+    pub fn new(commands: Sender<FeedCommand>) -> SubscriptionManager {
+        SubscriptionManager {
+            commands,
+            active: None,
+            watched: BTreeSet::new(),
+            peers: BTreeSet::new(),
+            rates: BTreeSet::new(),
+        }
+    }
+
+    /// Switches the feed over to a newly selected symbol
+    pub fn set_active(&mut self, symbol: &str) {
+        self.change(|manager| manager.active = Some(symbol.to_string()));
+    }
+
+    /// Streams `symbol` as well as the selected one
+    pub fn watch(&mut self, symbol: &str) {
+        self.change(|manager| {
+            manager.watched.insert(symbol.to_string());
+        });
+    }
+
+    /// Streams the peers of the selected symbol, replacing the previous ones
+    pub fn set_peers(&mut self, peers: &[String]) {
+        self.change(|manager| manager.peers = peers.iter().cloned().collect());
+    }
+
+    /// Streams the forex pairs behind the rate matrix, replacing the previous
+    /// ones. Empty while the matrix is not shown.
+    pub fn set_rates(&mut self, pairs: &[String]) {
+        self.change(|manager| manager.rates = pairs.iter().cloned().collect());
+    }
+
+    fn subscribed(&self) -> BTreeSet<String> {
+        self.active
+            .iter()
+            .chain(&self.watched)
+            .chain(&self.peers)
+            .chain(&self.rates)
+            .cloned()
+            .collect()
+    }
+
+    /// Applies `update` and sends whatever it changed to the feed
+    fn change<F: FnOnce(&mut SubscriptionManager)>(&mut self, update: F) {
+        let before = self.subscribed();
+        update(self);
+        let after = self.subscribed();
+        for symbol in before.difference(&after) {
+            self.send(FeedCommand::Unsubscribe(symbol.clone()));
+        }
+        for symbol in after.difference(&before) {
+            self.send(FeedCommand::Subscribe(symbol.clone()));
+        }
+    }
+
+    fn send(&self, command: FeedCommand) {
+        debug!("Live feed {:?}", command);
+        if self.commands.send(command).is_err() {
+            // Replays and fixtures don't take subscriptions
+            debug!("Live feed is not taking subscriptions");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver};
+
+    fn manager() -> (SubscriptionManager, Receiver<FeedCommand>) {
+        let (tx, rx) = mpsc::channel();
+        (SubscriptionManager::new(tx), rx)
+    }
+
+    fn sent(rx: &Receiver<FeedCommand>) -> Vec<FeedCommand> {
+        rx.try_iter().collect()
+    }
+
+    fn subscribe(symbol: &str) -> FeedCommand {
+        FeedCommand::Subscribe(symbol.to_string())
+    }
+
+    fn unsubscribe(symbol: &str) -> FeedCommand {
+        FeedCommand::Unsubscribe(symbol.to_string())
+    }
+
+    #[test]
+    fn switching_company_moves_the_subscription() {
+        let (mut manager, rx) = manager();
+        manager.set_active("AAPL");
+        assert_eq!(sent(&rx), vec![subscribe("AAPL")]);
+        manager.set_active("AAPL");
+        assert_eq!(sent(&rx), vec![]);
+        manager.set_active("TSLA");
+        assert_eq!(sent(&rx), vec![unsubscribe("AAPL"), subscribe("TSLA")]);
+    }
+
+    #[test]
+    fn shared_symbols_stay_until_the_last_source_drops_them() {
+        let (mut manager, rx) = manager();
+        manager.set_active("AAPL");
+        manager.set_peers(&[String::from("AAPL"), String::from("MSFT")]);
+        assert_eq!(sent(&rx), vec![subscribe("AAPL"), subscribe("MSFT")]);
+
+        // Still a peer, so the feed keeps streaming it
+        manager.set_active("GOOG");
+        assert_eq!(sent(&rx), vec![subscribe("GOOG")]);
+
+        manager.watch("MSFT");
+        manager.set_peers(&[]);
+        assert_eq!(sent(&rx), vec![unsubscribe("AAPL")]);
+    }
+
+    #[test]
+    fn rate_pairs_stream_while_the_matrix_is_shown() {
+        let (mut manager, rx) = manager();
+        manager.set_active("OANDA:EUR_USD");
+        let pairs = [String::from("OANDA:EUR_USD"), String::from("OANDA:GBP_USD")];
+        manager.set_rates(&pairs);
+        assert_eq!(
+            sent(&rx),
+            vec![subscribe("OANDA:EUR_USD"), subscribe("OANDA:GBP_USD")]
+        );
+        manager.set_rates(&[]);
+        assert_eq!(sent(&rx), vec![unsubscribe("OANDA:GBP_USD")]);
+    }
+
+    #[test]
+    fn a_stopped_feed_is_not_an_error() {
+        let (mut manager, rx) = manager();
+        drop(rx);
+        manager.set_active("AAPL");
+        assert_eq!(manager.subscribed().len(), 1);
+    }
+}
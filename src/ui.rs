@@ -1,27 +1,47 @@
 use crate::app::State;
-use crate::app::{App, InputMode};
-use crate::search_page::{SearchEngine};
+use crate::app::{
+    App, InputMode, ANALYSTS_TAB, CHART_TAB, DIVIDENDS_TAB, EARNINGS_TAB, FUNDAMENTALS_TAB,
+    INSIDERS_TAB, NEWS_TAB, PEERS_TAB, RATES_TAB,
+};
 use crate::asset;
+use crate::asset::{date_to_timestamp, AssetClass, EarningsRelease, RecommendationTrend};
+use crate::error::StonksError;
+use crate::market::{MarketStatus, Session};
+use crate::subscription::ConnectionStatus;
+use chrono::Utc;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use num_format::{Locale, ToFormattedString};
-#[allow(unused_imports)]
-use std::{sync::mpsc::Receiver, time::Duration};
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     symbols,
-    widgets::{Axis, Block, Borders, Chart, Dataset, Paragraph, Text},
+    widgets::{
+        Axis, Block, Borders, Chart, Dataset, GraphType, List, ListState, Paragraph, Row, Table,
+        Tabs, Text,
+    },
     Frame,
 };
 
-pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App, wsrx: &Receiver<(String, f64)>) {
-    let search_box_size: u16;
-    match app.state {
-        State::Search => search_box_size = 7,
-        State::Normal => search_box_size = 0,
-    }
+/// Matches listed under the search box
+const SEARCH_SUGGESTIONS: usize = 5;
+/// Past quarters in the earnings surprise chart
+const SURPRISE_QUARTERS: usize = 8;
+/// Points in the vertical line marking an earnings report on the chart
+const MARKER_POINTS: usize = 40;
+/// Most recent periods in a metric's sparkline
+const SPARKLINE_PERIODS: usize = 24;
+/// Most recent months of analyst ratings shown
+const RATING_MONTHS: usize = 12;
+/// Windows net insider buying is summarised over, in days
+const INSIDER_WINDOWS: [(&str, i64); 3] = [("3 months", 91), ("6 months", 182), ("12 months", 365)];
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &App) {
+    let search_box_size: u16 = match app.state {
+        State::Search => 7,
+        State::Normal => 0,
+    };
 
     let chunks = Layout::default()
         .constraints(
@@ -36,12 +56,78 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App, wsrx: &Receiver<(String
         .split(f.size());
 
     let msg = match app.input_mode {
-        InputMode::Normal => "Press / to search for an asset",
+        InputMode::Normal if app.tabs.index == NEWS_TAB => {
+            "Press ↑/↓ to pick an article, o to open it, c to copy its link, ←/→ to switch tabs"
+        }
+        InputMode::Normal if app.tabs.index == INSIDERS_TAB => {
+            "Press ↑/↓ to scroll the transactions, ←/→ to switch tabs"
+        }
+        InputMode::Normal if app.tabs.index == PEERS_TAB => {
+            "Press ↑/↓ to pick a peer, Enter to switch to it, ←/→ to switch tabs"
+        }
+        InputMode::Normal => {
+            "Press / to search for an asset, 1-8 to change the chart range, a to toggle split \
+             adjustment, ←/→ to switch tabs"
+        }
         InputMode::Editing => "Press Esc to stop editing, Enter to search",
     };
+    let header = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(32),
+                Constraint::Length(24),
+                Constraint::Length(20),
+            ]
+            .as_ref(),
+        )
+        .split(chunks[0]);
     let text = [Text::raw(msg)];
     let help_message = Paragraph::new(text.iter());
-    f.render_widget(help_message, chunks[0]);
+    f.render_widget(help_message, header[0]);
+
+    let market = app.market();
+    let status = market.status(Utc::now());
+    let color = match status.session {
+        Session::Open => Color::Green,
+        Session::PreMarket | Session::Lunch | Session::AfterHours => Color::Yellow,
+        Session::Closed => Color::Red,
+    };
+    let text = [Text::styled(
+        format!("{} {}", market.name(), status.label()),
+        Style::default().fg(color),
+    )];
+    let market_message = Paragraph::new(text.iter()).alignment(Alignment::Right);
+    f.render_widget(market_message, header[1]);
+
+    let (status, color) = match app.feed_status {
+        ConnectionStatus::Connecting => (String::from("Live connecting"), Color::Gray),
+        ConnectionStatus::Connected => (String::from("Live connected"), Color::Green),
+        ConnectionStatus::Reconnecting { attempt } => {
+            (format!("Live reconnecting ({})", attempt), Color::Yellow)
+        }
+        ConnectionStatus::Offline => (String::from("Live offline"), Color::Red),
+    };
+    let text = [Text::styled(status, Style::default().fg(color))];
+    let status_message = Paragraph::new(text.iter()).alignment(Alignment::Right);
+    f.render_widget(status_message, header[2]);
+
+    if let Some(quota) = app.service.quota() {
+        let color = if quota.remaining == 0 {
+            Color::Red
+        } else if quota.remaining * 5 < quota.limit {
+            Color::Yellow
+        } else {
+            Color::Gray
+        };
+        let text = [Text::styled(
+            format!("API quota {}/{}", quota.remaining, quota.limit),
+            Style::default().fg(color),
+        )];
+        let quota_message = Paragraph::new(text.iter()).alignment(Alignment::Right);
+        f.render_widget(quota_message, header[3]);
+    }
 
     let border_stlye = match app.input_mode {
         InputMode::Normal => Style::default().fg(Color::Rgb(230, 126, 34)), //carrot orange
@@ -51,25 +137,30 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App, wsrx: &Receiver<(String
     let mut text: Vec<tui::widgets::Text> = Vec::new();
     if let Some(search_engine) = &app.search_engine {
         if !app.input.is_empty() {
-            let mut names = search_engine.engine.search(&app.input);
-            let mut short_names: Vec<asset::Stock> = Vec::new();
-            short_names.append(&mut names[0..5].to_vec());
-            for suggestion in short_names{
-                text.append(&mut [Text::raw(format!("\n{}",suggestion.description))].to_vec())
+            for suggestion in search_engine.suggestions(&app.input, SEARCH_SUGGESTIONS) {
+                text.push(Text::raw(format!(
+                    "\n{} - {} ({})",
+                    suggestion.display_symbol, suggestion.description, suggestion.exchange
+                )));
             }
         }
     }
     let search_suggestion = Paragraph::new(text.iter());
-    
+
     f.render_widget(search_suggestion, chunks[2]);
 
+    let search_title = if app.missing_symbols.is_empty() {
+        String::from("Search")
+    } else {
+        format!("Search - could not load {}", app.missing_symbols.join(", "))
+    };
     let text = [Text::raw(&app.input)];
     let input = Paragraph::new(text.iter())
         .style(Style::default().fg(Color::Yellow))
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Search")
+                .title(&search_title)
                 .border_style(border_stlye)
                 .title_style(Style::default().fg(Color::Blue)),
         )
@@ -77,25 +168,859 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App, wsrx: &Receiver<(String
 
     f.render_widget(input, chunks[1]);
 
+    let main = Layout::default()
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(chunks[3]);
+    let tabs = Tabs::default()
+        .titles(&app.tabs.titles)
+        .select(app.tabs.index)
+        .style(Style::default().fg(Color::Gray))
+        .highlight_style(Style::default().fg(Color::Yellow).modifier(Modifier::BOLD));
+    f.render_widget(tabs, main[0]);
+
     match app.tabs.index {
-        0 => draw_first_tab(f, app, chunks[3], &wsrx),
+        CHART_TAB => draw_first_tab(f, app, main[1]),
+        RATES_TAB => draw_rates_tab(f, app, main[1]),
+        NEWS_TAB => draw_news_tab(f, app, main[1]),
+        EARNINGS_TAB => draw_earnings_tab(f, app, main[1]),
+        FUNDAMENTALS_TAB => draw_fundamentals_tab(f, app, main[1]),
+        ANALYSTS_TAB => draw_analysts_tab(f, app, main[1]),
+        PEERS_TAB => draw_peers_tab(f, app, main[1]),
+        DIVIDENDS_TAB => draw_dividends_tab(f, app, main[1]),
+        INSIDERS_TAB => draw_insiders_tab(f, app, main[1]),
         _ => {}
     };
 }
 
-fn draw_first_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect, wsrx: &Receiver<(String, f64)>)
+fn draw_first_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
 where
     B: Backend,
 {
     let chunks = Layout::default()
-        .constraints([Constraint::Percentage(60), Constraint::Length(7)].as_ref())
+        .constraints([Constraint::Percentage(60), Constraint::Length(12)].as_ref())
         .split(area);
     //draw_gauges(f, app, chunks[0]);
     draw_charts(f, app, chunks[0]);
-    draw_text(f, chunks[1], app, &wsrx);
+    draw_text(f, chunks[1], app);
+}
+
+/// Matrix of exchange rates between the configured currencies, above the chart
+/// of the pair last picked from it
+fn draw_rates_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(app.currencies.len() as u16 + 4),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let selected = app.rate_pairs().get(app.rates_selection).copied();
+    let rows = app
+        .currencies
+        .iter()
+        .map(|from| {
+            let mut cells = vec![from.clone()];
+            for to in &app.currencies {
+                let rate = match &app.rates {
+                    Some(rates) if from != to => rates
+                        .rate(from, to)
+                        .map_or_else(|| String::from("-"), |rate| format!("{:.4}", rate)),
+                    _ => String::from("-"),
+                };
+                if selected == Some((from.as_str(), to.as_str())) {
+                    cells.push(format!("[{}]", rate));
+                } else {
+                    cells.push(rate);
+                }
+            }
+            let style = match selected {
+                Some((selected_from, _)) if selected_from == from => {
+                    Style::default().fg(Color::Yellow)
+                }
+                _ => Style::default(),
+            };
+            Row::StyledData(cells.into_iter(), style)
+        })
+        .collect::<Vec<_>>();
+
+    let mut header = vec![String::new()];
+    header.extend(app.currencies.iter().cloned());
+    let widths = vec![Constraint::Length(12); app.currencies.len() + 1];
+    let title = match &app.rates {
+        Some(rates) => format!(
+            "Rates - 1 row currency in column currency, {} based",
+            rates.base
+        ),
+        None => String::from("Rates - loading"),
+    };
+    let table = Table::new(header.into_iter(), rows.into_iter())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(&title)
+                .title_style(Style::default().fg(Color::Cyan).modifier(Modifier::BOLD)),
+        )
+        .header_style(Style::default().fg(Color::Blue))
+        .widths(&widths);
+    f.render_widget(table, chunks[0]);
+
+    draw_charts(f, app, chunks[1]);
+}
+
+/// Recent headlines next to the summary and link of the selected one
+fn draw_news_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .direction(Direction::Horizontal)
+        .split(area);
+
+    let items = app.news.iter().map(|article| {
+        Text::raw(format!(
+            "{} {} - {}",
+            asset::timestamp_to_date(article.datetime).format("%d %b"),
+            article.source,
+            article.headline
+        ))
+    });
+    let title = match &app.news_status {
+        Some(status) => format!("News - {} - {}", app.symbol, status),
+        None if app.news.is_empty() => format!("News - {} - nothing yet", app.symbol),
+        None => format!("News - {}", app.symbol),
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(&title)
+                .title_style(Style::default().fg(Color::Cyan).modifier(Modifier::BOLD)),
+        )
+        .highlight_style(Style::default().fg(Color::Yellow).modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    let mut state = ListState::default();
+    if !app.news.is_empty() {
+        state.select(Some(app.news_selection));
+    }
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    let label = Style::default().fg(Color::Blue);
+    let mut text = vec![];
+    if let Some(article) = app.news.get(app.news_selection) {
+        text.push(Text::styled(
+            format!("{}\n", article.headline),
+            Style::default().modifier(Modifier::BOLD),
+        ));
+        text.push(Text::styled(
+            format!(
+                "{} · {}\n\n",
+                article.source,
+                asset::timestamp_to_date(article.datetime).format("%d %b %H:%M")
+            ),
+            label,
+        ));
+        text.push(Text::raw(format!("{}\n\n", article.summary)));
+        text.push(Text::styled(
+            article.url.clone(),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Article")
+        .title_style(Style::default().fg(Color::Magenta).modifier(Modifier::BOLD));
+    let paragraph = Paragraph::new(text.iter()).block(block).wrap(true);
+    f.render_widget(paragraph, chunks[1]);
 }
 
-fn draw_charts<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+/// Upcoming reports of the company and the watchlist next to its past
+/// surprises, above a chart of actual against estimated EPS
+fn draw_earnings_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .constraints([Constraint::Percentage(50), Constraint::Min(0)].as_ref())
+        .split(area);
+    let tables = Layout::default()
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .direction(Direction::Horizontal)
+        .split(chunks[0]);
+    let title_style = Style::default().fg(Color::Cyan).modifier(Modifier::BOLD);
+
+    let today = Utc::now().format("%Y-%m-%d").to_string();
+    let mut upcoming: Vec<&EarningsRelease> = app
+        .earnings
+        .iter()
+        .flat_map(|earnings| earnings.releases.iter())
+        .filter(|release| release.date >= today)
+        .collect();
+    for release in &app.watchlist_earnings {
+        if !upcoming
+            .iter()
+            .any(|known| known.symbol == release.symbol && known.date == release.date)
+        {
+            upcoming.push(release);
+        }
+    }
+    upcoming.sort_by(|a, b| a.date.cmp(&b.date));
+    let rows = upcoming.iter().map(|release| {
+        let style = if release.symbol == app.symbol {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let cells = vec![
+            release.date.clone(),
+            release.symbol.clone(),
+            report_hour(&release.hour).to_string(),
+            release
+                .eps_estimate
+                .map_or_else(|| String::from("-"), |eps| format!("{:.2}", eps)),
+            release
+                .revenue_estimate
+                .map_or_else(|| String::from("-"), format_amount),
+        ];
+        Row::StyledData(cells.into_iter(), style)
+    });
+    let title = if upcoming.is_empty() {
+        "Upcoming earnings - none in the next quarter"
+    } else {
+        "Upcoming earnings"
+    };
+    let widths = [
+        Constraint::Length(11),
+        Constraint::Length(8),
+        Constraint::Length(12),
+        Constraint::Length(8),
+        Constraint::Length(9),
+    ];
+    let table = Table::new(
+        ["Date", "Symbol", "Time", "EPS est", "Rev est"].iter(),
+        rows,
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .title_style(title_style),
+    )
+    .header_style(Style::default().fg(Color::Blue))
+    .widths(&widths);
+    f.render_widget(table, tables[0]);
+
+    let surprises = match &app.earnings {
+        Some(earnings) => &earnings.surprises[..],
+        None => &[],
+    };
+    let rows = surprises.iter().map(|surprise| {
+        let (text, color) = match (surprise.surprise(), surprise.surprise_percent()) {
+            (Some(diff), Some(percent)) => (format!("{:+.2} ({:+.1}%)", diff, percent), diff),
+            (Some(diff), None) => (format!("{:+.2}", diff), diff),
+            _ => (String::from("-"), 0.0),
+        };
+        let style = if color > 0.0 {
+            Style::default().fg(Color::Green)
+        } else if color < 0.0 {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default()
+        };
+        let cells = vec![
+            surprise.period.clone(),
+            surprise
+                .actual
+                .map_or_else(|| String::from("-"), |eps| format!("{:.2}", eps)),
+            surprise
+                .estimate
+                .map_or_else(|| String::from("-"), |eps| format!("{:.2}", eps)),
+            text,
+        ];
+        Row::StyledData(cells.into_iter(), style)
+    });
+    let title = match (app.asset_class, &app.earnings) {
+        (AssetClass::Stock, None) => format!("Surprises - {} - loading", app.symbol),
+        (AssetClass::Stock, Some(_)) if surprises.is_empty() => {
+            format!("Surprises - {} - none", app.symbol)
+        }
+        (AssetClass::Stock, Some(_)) => format!("Surprises - {}", app.symbol),
+        _ => String::from("Surprises - only stocks report earnings"),
+    };
+    let widths = [
+        Constraint::Length(11),
+        Constraint::Length(8),
+        Constraint::Length(9),
+        Constraint::Length(16),
+    ];
+    let table = Table::new(["Quarter", "Actual", "Estimate", "Surprise"].iter(), rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(&title)
+                .title_style(title_style),
+        )
+        .header_style(Style::default().fg(Color::Blue))
+        .widths(&widths);
+    f.render_widget(table, tables[1]);
+
+    // Oldest quarter on the left
+    let quarters: Vec<_> = surprises.iter().take(SURPRISE_QUARTERS).rev().collect();
+    let points = |eps: fn(&asset::EarningsSurprise) -> Option<f64>| {
+        quarters
+            .iter()
+            .enumerate()
+            .filter_map(|(i, surprise)| Some((i as f64, eps(surprise)?)))
+            .collect::<Vec<_>>()
+    };
+    let estimates = points(|surprise| surprise.estimate);
+    let actuals = points(|surprise| surprise.actual);
+    let values: Vec<f64> = estimates
+        .iter()
+        .chain(actuals.iter())
+        .map(|(_, eps)| *eps)
+        .collect();
+    let min = values.iter().cloned().fold(0.0, f64::min);
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    let margin = ((max - min) * 0.1).max(0.01);
+    let (min, max) = (min - margin, max + margin);
+    let x_labels: Vec<String> = match (quarters.first(), quarters.last()) {
+        (Some(first), Some(last)) => vec![first.period.clone(), last.period.clone()],
+        _ => Vec::new(),
+    };
+    let y_labels = [format!("{:.2}", min), format!("{:.2}", max)];
+    let datasets = [
+        Dataset::default()
+            .name("Estimate")
+            .marker(symbols::Marker::Dot)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Gray))
+            .data(&estimates),
+        Dataset::default()
+            .name("Actual")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .data(&actuals),
+    ];
+    let chart = Chart::default()
+        .block(
+            Block::default()
+                .title("EPS - actual against estimate")
+                .title_style(title_style)
+                .borders(Borders::ALL),
+        )
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, quarters.len().saturating_sub(1).max(1) as f64])
+                .labels(&x_labels),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([min, max])
+                .labels(&y_labels),
+        )
+        .datasets(&datasets);
+    f.render_widget(chart, chunks[1]);
+}
+
+/// Key metrics with a sparkline of their history, above the chart
+fn draw_fundamentals_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .constraints([Constraint::Length(14), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let rows = match &app.fundamentals {
+        Some(Ok(fundamentals)) => {
+            let metric = &fundamentals.metric;
+            let number = |value: Option<f64>| {
+                value.map_or_else(|| String::from("-"), |value| format!("{:.2}", value))
+            };
+            let percent = |value: Option<f64>| {
+                value.map_or_else(|| String::from("-"), |value| format!("{:.2}%", value))
+            };
+            let price = |value: Option<f64>| value.map_or_else(|| String::from("-"), format_price);
+            let history = |quarterly, annual| fundamentals.history(quarterly, annual);
+            vec![
+                ("P/E", number(metric.pe), history("peTTM", "pe")),
+                ("EPS", number(metric.eps), history("eps", "eps")),
+                ("Dividend yield", percent(metric.dividend_yield), Vec::new()),
+                ("Beta", number(metric.beta), Vec::new()),
+                ("52 week high", price(metric.year_high), Vec::new()),
+                ("52 week low", price(metric.year_low), Vec::new()),
+                (
+                    "Gross margin",
+                    percent(metric.gross_margin),
+                    history("grossMargin", "grossMargin"),
+                ),
+                (
+                    "Operating margin",
+                    percent(metric.operating_margin),
+                    history("operatingMargin", "operatingMargin"),
+                ),
+                (
+                    "Net margin",
+                    percent(metric.net_margin),
+                    history("netMargin", "netMargin"),
+                ),
+                (
+                    "Return on equity",
+                    percent(metric.roe),
+                    history("roeTTM", "roe"),
+                ),
+            ]
+        }
+        _ => Vec::new(),
+    };
+    let rows = rows.into_iter().map(|(label, value, history)| {
+        let recent = &history[history.len().saturating_sub(SPARKLINE_PERIODS)..];
+        Row::Data(vec![label.to_string(), value, sparkline(recent)].into_iter())
+    });
+    let title = match (app.asset_class, &app.fundamentals) {
+        (AssetClass::Stock, None) => format!("Fundamentals - {} - loading", app.symbol),
+        (AssetClass::Stock, Some(Err(err))) => format!("Fundamentals - {} - {}", app.symbol, err),
+        (AssetClass::Stock, Some(Ok(_))) => format!("Fundamentals - {}", app.symbol),
+        _ => String::from("Fundamentals - only stocks have fundamentals"),
+    };
+    let widths = [
+        Constraint::Length(18),
+        Constraint::Length(10),
+        Constraint::Length(SPARKLINE_PERIODS as u16),
+    ];
+    let table = Table::new(["Metric", "Value", "History"].iter(), rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(&title)
+                .title_style(Style::default().fg(Color::Cyan).modifier(Modifier::BOLD)),
+        )
+        .header_style(Style::default().fg(Color::Blue))
+        .widths(&widths);
+    f.render_widget(table, chunks[0]);
+
+    draw_charts(f, app, chunks[1]);
+}
+
+/// Monthly analyst ratings as stacked bars next to the consensus price
+/// target, above the chart with the targets drawn on it
+fn draw_analysts_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(RATING_MONTHS as u16 + 3),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    let panels = Layout::default()
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+        .direction(Direction::Horizontal)
+        .split(chunks[0]);
+    let title_style = Style::default().fg(Color::Cyan).modifier(Modifier::BOLD);
+    let analysts = match &app.analysts {
+        Some(Ok(analysts)) => Some(analysts),
+        _ => None,
+    };
+
+    let ratings = [
+        ("Strong buy", Color::Green),
+        ("Buy", Color::LightGreen),
+        ("Hold", Color::Yellow),
+        ("Sell", Color::LightRed),
+        ("Strong sell", Color::Red),
+    ];
+    let mut text = vec![];
+    for (label, color) in ratings.iter() {
+        text.push(Text::styled("■ ", Style::default().fg(*color)));
+        text.push(Text::raw(format!("{}  ", label)));
+    }
+    text.push(Text::raw("\n"));
+    // Month, total and borders around the bar
+    let bar_width = panels[0].width.saturating_sub(16) as usize;
+    let trends = analysts.map_or(&[][..], |analysts| &analysts.recommendations[..]);
+    for trend in &trends[trends.len().saturating_sub(RATING_MONTHS)..] {
+        text.push(Text::raw(format!(
+            "{} ",
+            trend.period.get(..7).unwrap_or(&trend.period)
+        )));
+        for (width, (_, color)) in rating_widths(trend, bar_width).iter().zip(ratings.iter()) {
+            text.push(Text::styled(
+                "█".repeat(*width),
+                Style::default().fg(*color),
+            ));
+        }
+        text.push(Text::raw(format!(" {}\n", trend.total())));
+    }
+    let title = match (app.asset_class, &app.analysts) {
+        (AssetClass::Stock, None) => format!("Ratings - {} - loading", app.symbol),
+        (AssetClass::Stock, Some(Err(err))) => format!("Ratings - {} - {}", app.symbol, err),
+        (AssetClass::Stock, Some(Ok(_))) => format!("Ratings - {}", app.symbol),
+        _ => String::from("Ratings - only stocks are rated"),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(&title)
+        .title_style(title_style);
+    f.render_widget(Paragraph::new(text.iter()).block(block), panels[0]);
+
+    let label = Style::default().fg(Color::Blue);
+    let mut text = vec![];
+    if let Some(target) = analysts.and_then(|analysts| analysts.price_target.as_ref()) {
+        for (name, value) in [
+            ("High", target.high),
+            ("Mean", target.mean),
+            ("Median", target.median),
+            ("Low", target.low),
+        ]
+        .iter()
+        {
+            text.push(Text::styled(format!("{}: ", name), label));
+            text.push(Text::raw(format!("{}\n", format_price(*value))));
+        }
+        let price = match (&app.quote, &app.company) {
+            (Some(quote), _) => Some(quote.current),
+            (None, Some(company)) => company.prices.last_close(),
+            (None, None) => None,
+        };
+        if let Some(price) = price.filter(|price| *price > 0.0) {
+            let upside = (target.mean / price - 1.0) * 100.0;
+            let color = if upside >= 0.0 {
+                Color::Green
+            } else {
+                Color::Red
+            };
+            text.push(Text::styled("Upside to mean: ", label));
+            text.push(Text::styled(
+                format!("{:+.2}%\n", upside),
+                Style::default().fg(color),
+            ));
+        }
+        text.push(Text::styled("Updated: ", label));
+        text.push(Text::raw(target.last_updated.clone()));
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Price target")
+        .title_style(title_style);
+    f.render_widget(Paragraph::new(text.iter()).block(block), panels[1]);
+
+    draw_charts(f, app, chunks[1]);
+}
+
+/// Widths of the strong buy to strong sell parts of a bar `width` long. The
+/// rounding is carried along so the parts always add up to the whole bar. A
+/// month without ratings gets no bar at all.
+fn rating_widths(trend: &RecommendationTrend, width: usize) -> [usize; 5] {
+    let counts = [
+        trend.strong_buy,
+        trend.buy,
+        trend.hold,
+        trend.sell,
+        trend.strong_sell,
+    ];
+    let total = trend.total();
+    let mut widths = [0; 5];
+    if total == 0 {
+        return widths;
+    }
+    let mut count_so_far = 0;
+    let mut width_so_far = 0;
+    for (i, count) in counts.iter().enumerate() {
+        count_so_far += count;
+        let end = (count_so_far as usize * width + total as usize / 2) / total as usize;
+        widths[i] = end - width_so_far;
+        width_so_far = end;
+    }
+    widths
+}
+
+/// Peers with their live price and day change, above the chart
+fn draw_peers_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let peers = match &app.peers {
+        Some(Ok(peers)) => &peers[..],
+        _ => &[],
+    };
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(peers.len().max(1) as u16 + 3),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let rows = peers.iter().enumerate().map(|(i, peer)| {
+        let live = app
+            .live
+            .get(&peer.symbol)
+            .map(|live| live.price)
+            .filter(|price| *price > 0.0);
+        let price = live.or_else(|| peer.quote.as_ref().map(|quote| quote.current));
+        let previous_close = peer
+            .quote
+            .as_ref()
+            .map(|quote| quote.previous_close)
+            .filter(|close| *close > 0.0);
+        let (change, color) = match (price, previous_close) {
+            (Some(price), Some(close)) => {
+                let change = price - close;
+                let color = if change >= 0.0 {
+                    Color::Green
+                } else {
+                    Color::Red
+                };
+                (
+                    format!("{:+.2} ({:+.2}%)", change, change / close * 100.0),
+                    color,
+                )
+            }
+            _ => (String::from("-"), Color::Reset),
+        };
+        let selected = i == app.peers_selection;
+        let cells = vec![
+            format!("{}{}", if selected { "> " } else { "  " }, peer.symbol),
+            price.map_or_else(|| String::from("-"), format_price),
+            change,
+            String::from(if live.is_some() { "live" } else { "" }),
+        ];
+        let style = if selected {
+            Style::default().fg(color).modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(color)
+        };
+        Row::StyledData(cells.into_iter(), style)
+    });
+    let title = match (app.asset_class, &app.peers) {
+        (AssetClass::Stock, None) => format!("Peers - {} - loading", app.symbol),
+        (AssetClass::Stock, Some(Err(err))) => format!("Peers - {} - {}", app.symbol, err),
+        (AssetClass::Stock, Some(Ok(_))) if peers.is_empty() => {
+            format!("Peers - {} - none", app.symbol)
+        }
+        (AssetClass::Stock, Some(Ok(_))) => format!("Peers - {}", app.symbol),
+        _ => String::from("Peers - only stocks have peers"),
+    };
+    let widths = [
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(18),
+        Constraint::Length(5),
+    ];
+    let table = Table::new(["Symbol", "Price", "Day change", ""].iter(), rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(&title)
+                .title_style(Style::default().fg(Color::Cyan).modifier(Modifier::BOLD)),
+        )
+        .header_style(Style::default().fg(Color::Blue))
+        .widths(&widths);
+    f.render_widget(table, chunks[0]);
+
+    draw_charts(f, app, chunks[1]);
+}
+
+/// Dividends and splits newest first, above the chart they are marked on
+fn draw_dividends_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .constraints([Constraint::Length(12), Constraint::Min(0)].as_ref())
+        .split(area);
+    let tables = Layout::default()
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+        .direction(Direction::Horizontal)
+        .split(chunks[0]);
+    let title_style = Style::default().fg(Color::Cyan).modifier(Modifier::BOLD);
+    let actions = match &app.actions {
+        Some(Ok(actions)) => Some(actions),
+        _ => None,
+    };
+    let status = match (app.asset_class, &app.actions) {
+        (AssetClass::Stock, None) => String::from(" - loading"),
+        (AssetClass::Stock, Some(Err(err))) => format!(" - {}", err),
+        (AssetClass::Stock, Some(Ok(_))) => String::new(),
+        _ => String::from(" - only stocks pay dividends"),
+    };
+
+    let dividends = actions.map_or(&[][..], |actions| &actions.dividends[..]);
+    let rows = dividends.iter().rev().map(|dividend| {
+        let cells = vec![
+            dividend.date.clone(),
+            format!("{:.4} {}", dividend.amount, dividend.currency),
+            dividend
+                .pay_date
+                .clone()
+                .unwrap_or_else(|| String::from("-")),
+        ];
+        Row::Data(cells.into_iter())
+    });
+    let title = format!("Dividends - {}{}", app.symbol, status);
+    let widths = [
+        Constraint::Length(11),
+        Constraint::Length(12),
+        Constraint::Length(11),
+    ];
+    let table = Table::new(["Ex date", "Amount", "Paid"].iter(), rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(&title)
+                .title_style(title_style),
+        )
+        .header_style(Style::default().fg(Color::Blue))
+        .widths(&widths);
+    f.render_widget(table, tables[0]);
+
+    let splits = actions.map_or(&[][..], |actions| &actions.splits[..]);
+    let rows = splits.iter().rev().map(|split| {
+        let cells = vec![
+            split.date.clone(),
+            format!("{}:{}", split.to_factor, split.from_factor),
+        ];
+        Row::Data(cells.into_iter())
+    });
+    let title = match app.splits() {
+        Some(_) if app.adjusted => "Splits - chart adjusted",
+        Some(_) => "Splits - chart unadjusted",
+        None => "Splits",
+    };
+    let widths = [Constraint::Length(11), Constraint::Length(8)];
+    let table = Table::new(["Date", "Ratio"].iter(), rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .title_style(title_style),
+        )
+        .header_style(Style::default().fg(Color::Blue))
+        .widths(&widths);
+    f.render_widget(table, tables[1]);
+
+    draw_charts(f, app, chunks[1]);
+}
+
+/// Net open market buying over the last few months above every insider
+/// transaction of the last year
+fn draw_insiders_tab<B>(f: &mut Frame<B>, app: &App, area: Rect)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .constraints([Constraint::Length(5), Constraint::Min(0)].as_ref())
+        .split(area);
+    let title_style = Style::default().fg(Color::Cyan).modifier(Modifier::BOLD);
+    let transactions = match &app.insiders {
+        Some(Ok(transactions)) => &transactions[..],
+        _ => &[][..],
+    };
+    let status = match (app.asset_class, &app.insiders) {
+        (AssetClass::Stock, None) => String::from(" - loading"),
+        (AssetClass::Stock, Some(Err(err))) => format!(" - {}", err),
+        (AssetClass::Stock, Some(Ok(_))) => String::new(),
+        _ => String::from(" - only stocks have insiders"),
+    };
+
+    let today = Utc::now().date_naive();
+    let mut text = Vec::new();
+    for (label, days) in INSIDER_WINDOWS.iter() {
+        let since = (today - chrono::Duration::days(*days))
+            .format("%Y-%m-%d")
+            .to_string();
+        let (shares, value) = asset::net_insider_buying(transactions, &since);
+        let color = if shares > 0.0 {
+            Color::Green
+        } else if shares < 0.0 {
+            Color::Red
+        } else {
+            Color::Gray
+        };
+        text.push(Text::raw(format!("{:<11}", label)));
+        text.push(Text::styled(
+            format!(
+                "{:>10} shares {:>10} value\n",
+                signed_amount(shares),
+                signed_amount(value)
+            ),
+            Style::default().fg(color),
+        ));
+    }
+    let title = format!("Net insider buying - {}{}", app.symbol, status);
+    let summary = Paragraph::new(text.iter()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(&title)
+            .title_style(title_style),
+    );
+    f.render_widget(summary, chunks[0]);
+
+    let rows = transactions
+        .iter()
+        .skip(app.insiders_scroll)
+        .map(|transaction| {
+            let color = match transaction.transaction_code.as_str() {
+                "P" => Color::Green,
+                "S" => Color::Red,
+                _ => Color::Gray,
+            };
+            let price = transaction
+                .transaction_price
+                .filter(|price| *price > 0.0)
+                .map_or_else(|| String::from("-"), |price| format!("{:.2}", price));
+            let cells = vec![
+                transaction.name.clone(),
+                format!("{:+}", transaction.change as i64),
+                price,
+                transaction.transaction_date.clone(),
+                format!("{} ({})", transaction.kind(), transaction.transaction_code),
+                transaction.filing_date.clone(),
+            ];
+            Row::StyledData(cells.into_iter(), Style::default().fg(color))
+        });
+    let title = format!("Insider transactions ({})", transactions.len());
+    let widths = [
+        Constraint::Length(28),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(11),
+        Constraint::Length(14),
+        Constraint::Length(11),
+    ];
+    let table = Table::new(
+        ["Name", "Shares", "Price", "Date", "Type", "Filed"].iter(),
+        rows,
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(&title)
+            .title_style(title_style),
+    )
+    .header_style(Style::default().fg(Color::Blue))
+    .widths(&widths);
+    f.render_widget(table, chunks[1]);
+}
+
+fn draw_charts<B>(f: &mut Frame<B>, app: &App, area: Rect)
 where
     B: Backend,
 {
@@ -110,68 +1035,155 @@ where
         .split(area);
 
     if app.show_chart {
-        match &app.company {
-            None => {
-                let api_key = app.config.get("api_key").expect("No API Key in config");
-                match asset::get_equity(api_key, &app.symbol) {
-                    Err(err) => error!("Error getting {}", err),
-                    Ok(mut company) => {
-                        match asset::get_price_history(api_key, &app.symbol, "D") {
-                            Ok(res) => company.prices = res,
-                            _ => {}
-                        }
-                        app.company = Some(company);
-                    }
-                }
-            }
-            Some(_) => {}
+        if let Some(err) = &app.error {
+            draw_error(f, chunks[1], err);
+            return;
         }
+
         let mut data = Vec::<(f64, f64)>::new();
 
         let mut min = 0.0;
         let mut max = 300.0;
-        if let Some(company) = &app.company {
+        let prices = app.chart_prices();
+        if let Some(prices) = &prices {
             //info!("{:#?}",company);
-            data = label_data(&company.prices.close);
-            let price_range = get_range(&company.prices.close);
-            min = price_range.0;
-            max = price_range.1;
+            data = label_data(&prices.close);
+            // An empty series keeps the default bounds, the title says no data
+            if let Some((low, high)) = get_range(&prices.close) {
+                min = low;
+                max = high;
+            }
         }
 
-        let x_labels = [
-            format!("{}", 0),
-            format!("{}", data.len() / 10 * 1),
-            format!("{}", data.len() / 10 * 2),
-            format!("{}", data.len() / 10 * 3),
-            format!("{}", data.len() / 10 * 4),
-            format!("{}", data.len() / 10 * 5),
-            format!("{}", data.len() / 10 * 6),
-            format!("{}", data.len() / 10 * 7),
-            format!("{}", data.len() / 10 * 8),
-            format!("{}", data.len() / 10 * 9),
-            format!("{}", data.len()),
-        ];
+        let date_format = if app.chart_range.is_intraday() {
+            "%d %b %H:%M"
+        } else {
+            "%d %b %y"
+        };
+        let x_labels = match &prices {
+            Some(prices) => date_labels(prices, 5, date_format),
+            None => Vec::new(),
+        };
+        let title = match &prices {
+            _ if app.loading => format!("Chart - {} - loading", app.chart_range.label()),
+            Some(prices) if prices.is_empty() => {
+                format!("Chart - {} - no data", app.chart_range.label())
+            }
+            _ => {
+                let change = prices
+                    .as_ref()
+                    .and_then(|prices| prices.change_percent())
+                    .map_or_else(String::new, |change| format!(" {:+.2}%", change));
+                let adjusted = match app.splits() {
+                    Some(_) if app.adjusted => " - split adjusted",
+                    Some(_) => " - unadjusted",
+                    None => "",
+                };
+                format!("Chart - {}{}{}", app.chart_range.label(), change, adjusted)
+            }
+        };
 
-        let datasets = [Dataset::default()
-            .name("data2")
-            .marker(symbols::Marker::Braille)
-            .style(Style::default().fg(Color::Cyan))
-            .data(&data)];
-        let min_string = format!("{}", min as u16);
-        let mid_string = format!("{}", (max as u16 + min as u16) / 2);
-        let max_string = format!("{}", max as u16);
-        let labels = [min_string, mid_string, max_string];
+        // Analysts' price targets, the range is widened to keep them in view
+        let target = match &app.analysts {
+            Some(Ok(analysts)) if !data.is_empty() => analysts.price_target.as_ref(),
+            _ => None,
+        };
+        if let Some(target) = target {
+            min = f64::min(min, target.low);
+            max = f64::max(max, target.high);
+        }
+        let band = |price: Option<f64>| match price {
+            Some(price) => vec![(0.0, price), (data.len() as f64, price)],
+            None => Vec::new(),
+        };
+        let target_high = band(target.map(|target| target.high));
+        let target_mean = band(target.map(|target| target.mean));
+        let target_low = band(target.map(|target| target.low));
+
+        let mut markers = Vec::new();
+        let mut split_markers = Vec::new();
+        let mut dividend_markers = Vec::new();
+        if let Some(prices) = &prices {
+            if let Some(earnings) = &app.earnings {
+                let dates = earnings
+                    .releases
+                    .iter()
+                    .filter_map(EarningsRelease::timestamp);
+                markers = date_markers(prices, dates, min, max);
+            }
+            if let Some(Ok(actions)) = &app.actions {
+                let dates = actions
+                    .splits
+                    .iter()
+                    .filter_map(|split| date_to_timestamp(&split.date));
+                split_markers = date_markers(prices, dates, min, max);
+                // Dividends are too frequent for lines, they get a dot at the bottom
+                dividend_markers = actions
+                    .dividends
+                    .iter()
+                    .filter_map(|dividend| date_to_timestamp(&dividend.date))
+                    .filter_map(|timestamp| bar_index(prices, timestamp))
+                    .map(|index| (index as f64, min))
+                    .collect();
+            }
+        }
+
+        let datasets = [
+            Dataset::default()
+                .name("data2")
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(Color::Cyan))
+                .data(&data),
+            Dataset::default()
+                .name("earnings")
+                .marker(symbols::Marker::Dot)
+                .style(Style::default().fg(Color::Yellow))
+                .data(&markers),
+            Dataset::default()
+                .name("splits")
+                .marker(symbols::Marker::Dot)
+                .style(Style::default().fg(Color::LightMagenta))
+                .data(&split_markers),
+            Dataset::default()
+                .name("dividends")
+                .marker(symbols::Marker::Dot)
+                .style(Style::default().fg(Color::LightBlue))
+                .data(&dividend_markers),
+            Dataset::default()
+                .name("target high")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Green))
+                .data(&target_high),
+            Dataset::default()
+                .name("target mean")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Magenta))
+                .data(&target_mean),
+            Dataset::default()
+                .name("target low")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Red))
+                .data(&target_low),
+        ];
+        let labels = [
+            format_price(min),
+            format_price((min + max) / 2.0),
+            format_price(max),
+        ];
 
         let chart = Chart::default()
             .block(
                 Block::default()
-                    .title("Chart")
+                    .title(&title)
                     .title_style(Style::default().fg(Color::Cyan).modifier(Modifier::BOLD))
                     .borders(Borders::ALL),
             )
             .x_axis(
                 Axis::default()
-                    .title("X Axis")
+                    .title("Date")
                     .style(Style::default().fg(Color::Gray))
                     .labels_style(Style::default().modifier(Modifier::ITALIC))
                     .bounds([0.0, data.len() as f64])
@@ -190,15 +1202,42 @@ where
     }
 }
 
-fn draw_text<B>(f: &mut Frame<B>, area: Rect, app: &mut App, wsrx: &Receiver<(String, f64)>)
+fn draw_error<B>(f: &mut Frame<B>, area: Rect, err: &StonksError)
 where
     B: Backend,
 {
+    let text = [Text::styled(
+        format!("{}", err),
+        Style::default().fg(Color::Red),
+    )];
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Error")
+        .title_style(Style::default().fg(Color::Red).modifier(Modifier::BOLD));
+    let paragraph = Paragraph::new(text.iter()).block(block).wrap(true);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_text<B>(f: &mut Frame<B>, area: Rect, app: &App)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .direction(Direction::Horizontal)
+        .split(area);
+    draw_quote(f, chunks[1], app);
+
     let mut text = vec![];
+    let status = app.market().status(Utc::now());
 
-    match &mut app.company {
-        Some(company) => assemble_company_info(company, &mut text, wsrx),
-        None => {}
+    if let Some(company) = &app.company {
+        match company.asset_class {
+            AssetClass::Stock => {
+                assemble_company_info(company, app.live.get(&company.ticker), status, &mut text)
+            }
+            _ => assemble_pair_info(company, app.live.get(&company.ticker), status, &mut text),
+        }
     }
 
     let block = Block::default()
@@ -207,35 +1246,99 @@ where
         .title_style(Style::default().fg(Color::Magenta).modifier(Modifier::BOLD));
     let paragraph = Paragraph::new(text.iter()).block(block);
     //.wrap(Wrap { trim: true });
+    f.render_widget(paragraph, chunks[0]);
+}
+
+/// Price against the previous close and the day and 52 week ranges, coloured
+/// by the day's change. Falls back to the last close without a quote.
+fn draw_quote<B>(f: &mut Frame<B>, area: Rect, app: &App)
+where
+    B: Backend,
+{
+    let label = Style::default().fg(Color::Blue);
+    let mut text = vec![];
+    match (&app.quote, &app.company) {
+        (Some(quote), _) => {
+            let color = if quote.change() >= 0.0 {
+                Color::Green
+            } else {
+                Color::Red
+            };
+            let percent = quote
+                .change_percent()
+                .map_or_else(String::new, |percent| format!(" ({:+.2}%)", percent));
+            text.push(Text::styled("Price: ", label));
+            text.push(Text::styled(
+                format_price(quote.current),
+                Style::default().fg(color).modifier(Modifier::BOLD),
+            ));
+            text.push(Text::styled("\nChange: ", label));
+            text.push(Text::styled(
+                format!("{:+.2}{}", quote.change(), percent),
+                Style::default().fg(color),
+            ));
+            text.push(Text::styled("\nOpen: ", label));
+            text.push(Text::raw(format_price(quote.open)));
+            text.push(Text::styled("\nPrevious close: ", label));
+            text.push(Text::raw(format_price(quote.previous_close)));
+            text.push(Text::styled("\nDay range: ", label));
+            text.push(Text::raw(format!(
+                "{} - {}",
+                format_price(quote.low),
+                format_price(quote.high)
+            )));
+            text.push(Text::styled("\n52 week range: ", label));
+            text.push(Text::raw(quote.year_range.map_or_else(
+                || String::from("-"),
+                |(low, high)| format!("{} - {}", format_price(low), format_price(high)),
+            )));
+        }
+        (None, Some(company)) => {
+            if let Some(close) = company.prices.last_close() {
+                text.push(Text::styled("Price: ", label));
+                text.push(Text::raw(format_price(close)));
+            }
+        }
+        (None, None) => {}
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Quote")
+        .title_style(Style::default().fg(Color::Magenta).modifier(Modifier::BOLD));
+    let paragraph = Paragraph::new(text.iter()).block(block);
     f.render_widget(paragraph, area);
 }
 
-fn assemble_company_info<'a, 'b>(
-    company: &'a mut asset::CompanyInfo,
-    text: &'b mut Vec<Text<'a>>,
-    wsrx: &Receiver<(String, f64)>,
+/// Two decimals, more for prices below 10 like most exchange rates
+fn format_price(price: f64) -> String {
+    if price.abs() < 10.0 {
+        format!("{:.4}", price)
+    } else {
+        format!("{:.2}", price)
+    }
+}
+
+fn assemble_company_info<'a>(
+    company: &'a asset::CompanyInfo,
+    live: Option<&asset::LiveStats>,
+    status: MarketStatus,
+    text: &mut Vec<Text<'a>>,
 ) {
     text.push(Text::styled("Name: ", Style::default().fg(Color::Blue)));
-    text.push(Text::raw(format!("{}", company.name)));
-    text.push(Text::styled("\nPrice: ", Style::default().fg(Color::Blue)));
-    if company.prices.close.len() > 0 {
-        text.push(Text::raw(format!(
-            "{}",
-            company.prices.close[company.prices.close.len() - 1]
-        )));
-    }
+    text.push(Text::raw(&company.name));
     text.push(Text::styled("\nTicker: ", Style::default().fg(Color::Blue)));
-    text.push(Text::raw(format!("{}", company.ticker)));
+    text.push(Text::raw(&company.ticker));
     text.push(Text::styled(
         "\nCountry: ",
         Style::default().fg(Color::Blue),
     ));
-    text.push(Text::raw(format!("{}", company.country)));
+    text.push(Text::raw(&company.country));
     text.push(Text::styled(
         "\nExchange: ",
         Style::default().fg(Color::Blue),
     ));
-    text.push(Text::raw(format!("{}", company.exchange)));
+    text.push(Text::raw(&company.exchange));
     text.push(Text::styled(
         "\nMarket Cap: ",
         Style::default().fg(Color::Blue),
@@ -249,68 +1352,90 @@ fn assemble_company_info<'a, 'b>(
         "\nCurrency: ",
         Style::default().fg(Color::Blue),
     ));
-    text.push(Text::raw(format!("{}", company.currency)));
+    text.push(Text::raw(&company.currency));
     text.push(Text::styled(
         "\nIndustry: ",
         Style::default().fg(Color::Blue),
     ));
-    text.push(Text::raw(format!("{}", company.industry)));
+    text.push(Text::raw(&company.industry));
+
+    live_price_text(text, company, live, status);
+}
+
+/// Crypto pairs have no profile, so no market cap or industry either
+fn assemble_pair_info<'a>(
+    company: &'a asset::CompanyInfo,
+    live: Option<&asset::LiveStats>,
+    status: MarketStatus,
+    text: &mut Vec<Text<'a>>,
+) {
+    text.push(Text::styled("Name: ", Style::default().fg(Color::Blue)));
+    text.push(Text::raw(&company.name));
+    text.push(Text::styled("\nSymbol: ", Style::default().fg(Color::Blue)));
+    text.push(Text::raw(&company.ticker));
+    text.push(Text::styled(
+        "\nExchange: ",
+        Style::default().fg(Color::Blue),
+    ));
+    text.push(Text::raw(&company.exchange));
+    if let Some(volume) = company.prices.volume.last() {
+        text.push(Text::styled("\nVolume: ", Style::default().fg(Color::Blue)));
+        text.push(Text::raw(format!("{:.2}", volume)));
+    }
 
-    live_price_text(text, company, wsrx);
+    live_price_text(text, company, live, status);
 }
 
 fn live_price_text(
     text: &mut Vec<Text>,
-    company: &mut asset::CompanyInfo,
-    wsrx: &Receiver<(String, f64)>,
+    company: &asset::CompanyInfo,
+    live: Option<&asset::LiveStats>,
+    status: MarketStatus,
 ) {
-    let d = Duration::from_millis(0);
-    let res = wsrx.recv_timeout(d);
-    match res {
-        Ok(msg) => {
-            let (symbol, price) = msg;
-            text.push(Text::styled(
-                format!("\nLive - {} ", symbol),
-                Style::default().fg(Color::Blue),
-            ));
-            if price > company.prices.live_price {
-                text.push(Text::styled(
-                    format!("▲ {}", price),
-                    Style::default().fg(Color::Green),
-                ));
-                company.prices.movement_indicator = String::from("▲");
-            } else {
-                text.push(Text::styled(
-                    format!("▼ {}", price),
-                    Style::default().fg(Color::Red),
-                ));
-                company.prices.movement_indicator = String::from("▼");
-            }
-            company.prices.live_price = price;
-        }
-        Err(_e) => {
-            text.push(Text::styled(
-                format!("\nLive - {} ", company.ticker),
-                Style::default().fg(Color::Blue),
-            ));
-            let mut color = Color::Red;
-            if company.prices.movement_indicator == "▲" {
-                color = Color::Green;
-            }
+    text.push(Text::styled(
+        format!("\nLive - {} ", company.ticker),
+        Style::default().fg(Color::Blue),
+    ));
+    let mut color = Color::Red;
+    if company.prices.movement_indicator == "▲" {
+        color = Color::Green;
+    }
+    // The last trade before the close, not a quiet market
+    let stale = match status.session {
+        Session::Closed => Some("market closed"),
+        Session::Lunch => Some("lunch break"),
+        _ => None,
+    };
+    if stale.is_some() {
+        color = Color::DarkGray;
+    }
 
-            text.push(Text::styled(
-                format!(
-                    "{} {}",
-                    company.prices.movement_indicator, company.prices.live_price
-                ),
-                Style::default().fg(color),
-            ));
-            //error!("{}", e);
+    text.push(Text::styled(
+        format!(
+            "{} {}",
+            company.prices.movement_indicator, company.prices.live_price
+        ),
+        Style::default().fg(color),
+    ));
+    if let Some(reason) = stale {
+        text.push(Text::styled(
+            format!("  stale, {}", reason),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    if let Some(live) = live {
+        text.push(Text::raw(format!(
+            "  Vol {}",
+            (live.volume as u64).to_formatted_string(&Locale::en)
+        )));
+        if let Some(vwap) = live.vwap() {
+            text.push(Text::raw(format!("  VWAP {:.2}", vwap)));
         }
     }
 }
 
-fn label_data(prices: &Vec<f64>) -> Vec<(f64, f64)> {
+fn label_data(prices: &[f64]) -> Vec<(f64, f64)> {
     let mut data: Vec<(f64, f64)> = Vec::new();
 
     for (i, price) in prices.iter().enumerate() {
@@ -319,18 +1444,139 @@ fn label_data(prices: &Vec<f64>) -> Vec<(f64, f64)> {
     data
 }
 
-fn get_range(prices: &Vec<f64>) -> (f64, f64) {
-    let mut min = std::u32::MAX as f64;
-    let mut max = 0.0;
+/// Index of the first bar on or after `timestamp`, if it falls within the
+/// bars
+fn bar_index(prices: &asset::Prices, timestamp: i64) -> Option<usize> {
+    let first = *prices.timestamps.first()?;
+    let last = *prices.timestamps.last()?;
+    // Days after the last bar have not been charted yet
+    if timestamp < first || timestamp > last {
+        return None;
+    }
+    prices.timestamps.iter().position(|bar| *bar >= timestamp)
+}
 
-    for price in prices {
-        if *price > max {
-            max = *price;
-        };
-        if *price < min {
-            min = *price;
+/// Vertical lines from `min` to `max` at the bars of the days starting at
+/// `timestamps`
+fn date_markers<I>(prices: &asset::Prices, timestamps: I, min: f64, max: f64) -> Vec<(f64, f64)>
+where
+    I: Iterator<Item = i64>,
+{
+    let mut points = Vec::new();
+    for index in timestamps.filter_map(|timestamp| bar_index(prices, timestamp)) {
+        for step in 0..=MARKER_POINTS {
+            let price = min + (max - min) * step as f64 / MARKER_POINTS as f64;
+            points.push((index as f64, price));
         }
     }
+    points
+}
 
-    (min, max)
+/// Block characters tracing `values` from their lowest to their highest
+fn sparkline(values: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            if max > min {
+                BARS[((value - min) / (max - min) * 7.0).round() as usize]
+            } else {
+                BARS[3]
+            }
+        })
+        .collect()
+}
+
+/// When in the day a report comes out
+fn report_hour(hour: &str) -> &str {
+    match hour {
+        "bmo" => "Before open",
+        "amc" => "After close",
+        "dmh" => "Market hours",
+        _ => "-",
+    }
+}
+
+/// Large amounts like revenue in thousands, millions or billions
+fn format_amount(amount: f64) -> String {
+    let size = amount.abs();
+    if size >= 1e9 {
+        format!("{:.2}B", amount / 1e9)
+    } else if size >= 1e6 {
+        format!("{:.2}M", amount / 1e6)
+    } else if size >= 1e3 {
+        format!("{:.2}K", amount / 1e3)
+    } else {
+        format!("{:.0}", amount)
+    }
+}
+
+/// Like `format_amount` with a plus sign in front of gains
+fn signed_amount(amount: f64) -> String {
+    if amount > 0.0 {
+        format!("+{}", format_amount(amount))
+    } else {
+        format_amount(amount)
+    }
+}
+
+/// Evenly spaced bar dates to label the x axis with
+fn date_labels(prices: &asset::Prices, count: usize, format: &str) -> Vec<String> {
+    if prices.timestamps.is_empty() || count < 2 {
+        return Vec::new();
+    }
+    let last = prices.timestamps.len() - 1;
+    (0..count)
+        .map(|i| {
+            let timestamp = prices.timestamps[last * i / (count - 1)];
+            asset::timestamp_to_date(timestamp)
+                .format(format)
+                .to_string()
+        })
+        .collect()
+}
+
+/// Lowest and highest price, `None` if there are none
+fn get_range(prices: &[f64]) -> Option<(f64, f64)> {
+    if prices.is_empty() {
+        return None;
+    }
+    let min = prices.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = prices.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    Some((min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trend(counts: [u32; 5]) -> RecommendationTrend {
+        RecommendationTrend {
+            period: String::from("2020-10-01"),
+            strong_buy: counts[0],
+            buy: counts[1],
+            hold: counts[2],
+            sell: counts[3],
+            strong_sell: counts[4],
+        }
+    }
+
+    #[test]
+    fn rating_widths_fill_the_bar() {
+        assert_eq!(rating_widths(&trend([1, 1, 1, 0, 0]), 10), [3, 4, 3, 0, 0]);
+        assert_eq!(rating_widths(&trend([5, 0, 0, 0, 0]), 7), [7, 0, 0, 0, 0]);
+        for width in 0..40 {
+            for counts in &[[1, 2, 3, 4, 5], [7, 0, 1, 0, 3], [0, 0, 0, 0, 1]] {
+                let widths = rating_widths(&trend(*counts), width);
+                assert_eq!(widths.iter().sum::<usize>(), width);
+            }
+        }
+    }
+
+    #[test]
+    fn rating_widths_without_ratings() {
+        assert_eq!(rating_widths(&trend([0; 5]), 20), [0; 5]);
+    }
 }
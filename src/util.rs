@@ -1,10 +1,12 @@
+use std::io::{self, Write};
+
 pub struct TabsState<'a> {
     pub titles: Vec<&'a str>,
     pub index: usize,
 }
 
 impl<'a> TabsState<'a> {
-    pub fn new(titles: Vec<&'a str>) -> TabsState {
+    pub fn new(titles: Vec<&'a str>) -> TabsState<'a> {
         TabsState { titles, index: 0 }
     }
     pub fn next(&mut self) {
@@ -19,3 +21,11 @@ impl<'a> TabsState<'a> {
         }
     }
 }
+
+/// Puts `text` on the clipboard with the OSC 52 escape sequence, which the
+/// terminal handles, so it works over ssh as well
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64::encode(text))?;
+    stdout.flush()
+}